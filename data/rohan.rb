@@ -1,6 +1,7 @@
 # frozen_string_literal: true
 #
-# https://github.com/mmorise/rohan4600 のコーパスをダウンロードして文章のみを抽出するスクリプト
+# https://github.com/mmorise/rohan4600 のコーパスをダウンロードするスクリプト
+# 読みの正解データとして使うため、文章と読みはそのまま残す（括弧の注記は比較時に除去する）
 require "open-uri"
 
 url = "https://raw.githubusercontent.com/mmorise/rohan4600/refs/heads/main/Rohan4600_transcript_utf8.txt"
@@ -8,6 +9,6 @@ destination = "rohan4600_transcript_utf8.txt"
 
 URI.open(url) do |f|
   content = f.read
-  File.binwrite(destination, content.gsub(/ROHAN4600_[0-9]+:/, "").gsub(/\(.+?\)/, "").gsub(/,.+$/, ""))
+  File.binwrite(destination, content)
   puts "Downloaded #{url} to #{destination}"
 end
@@ -2,7 +2,7 @@
 
 if ARGV.length != 3
   puts "Usage: ruby filter.rb <log> <target> <output>"
-  puts "target: light | fatal | jp_error | ojt_error"
+  puts "target: light | fatal | label | accent | jp_error | ojt_error"
   exit
 end
 
@@ -21,6 +21,10 @@ File.open(output, "w") do |output_file|
           current_level = "light"
         elsif line.include? "Fatal"
           current_level = "fatal"
+        elsif line.include? "Label mismatch"
+          current_level = "label"
+        elsif line.include? "Accent mismatch"
+          current_level = "accent"
         elsif line.include? "OpenJTalk Error"
           current_level = "jp_error"
         elsif line.include? "JPreprocess"
@@ -3,6 +3,23 @@ export type DiffKind = "none" | "light" | "fatal";
 export type Phoneme = {
   value: string;
   diff: DiffKind;
+  rule?: string;
+};
+
+export type AlignOp = "equal" | "equivalent" | "substitute" | "delete" | "insert";
+
+export type AlignedPhoneme = {
+  openjtalk?: string;
+  jpreprocess?: string;
+  op: AlignOp;
+  diff: DiffKind;
+  rule?: string;
+};
+
+export type MoraSegment = {
+  openjtalk: string;
+  jpreprocess: string;
+  diff: DiffKind;
 };
 
 export type Stats = {
@@ -11,6 +28,51 @@ export type Stats = {
   matches: number;
   lightMismatches: number;
   fatalMismatches: number;
+  labelMismatches: number;
+  accentMismatches?: number;
+  labelFieldMismatches: Record<string, number>;
+  expected: number;
+  segmentationMismatches: number;
+  segmentationPrecision: number;
+  segmentationRecall: number;
+  segmentationF1: number;
+  pauseMismatches?: number;
+  utteranceMismatches?: number;
+  interrogativeMismatches?: number;
+  wordMismatches?: number;
+  // "OpenJTalk's / jpreprocess's" part of speech to the number of words.
+  posConfusions?: Record<string, number>;
+  // Light and fatal mismatches by their likely cause.
+  mismatchCauses?: Partial<Record<Cause, number>>;
+  phonemes?: number;
+  phonemeSubstitutions?: number;
+  phonemeInsertions?: number;
+  phonemeDeletions?: number;
+  phonemeErrorRate?: number;
+  moras?: number;
+  moraSubstitutions?: number;
+  moraInsertions?: number;
+  moraDeletions?: number;
+  moraErrorRate?: number;
+  readingSentences?: number;
+  openjtalkMoraAccuracy?: number;
+  jpreprocessMoraAccuracy?: number;
+  accentSentences?: number;
+  openjtalkAccentAccuracy?: number;
+  jpreprocessAccentAccuracy?: number;
+  openjtalkAccentBoundaryF1?: number;
+  jpreprocessAccentBoundaryF1?: number;
+  rubySpans?: number;
+  openjtalkRubyAccuracy?: number;
+  jpreprocessRubyAccuracy?: number;
+  goldSentences?: number;
+  openjtalkPhonemeErrorRate?: number;
+  jpreprocessPhonemeErrorRate?: number;
+  openjtalkMoraErrorRate?: number;
+  jpreprocessMoraErrorRate?: number;
+  openjtalkExactMatchRate?: number;
+  jpreprocessExactMatchRate?: number;
+  unscoredSentences?: number;
   jpErrors: number;
   ojtErrors: number;
   openjtalkExtractionDurationMs: number;
@@ -19,21 +81,221 @@ export type Stats = {
   jpreprocessThroughputCharsPerSecond: number;
 };
 
+export type Segmentation = {
+  openjtalk: string[];
+  jpreprocess: string[];
+  precision: number;
+  recall: number;
+  f1: number;
+};
+
+export type ReadingScore = {
+  reference: string;
+  openjtalk: string;
+  jpreprocess: string;
+  moras: number;
+  openjtalkCorrect: number;
+  jpreprocessCorrect: number;
+};
+
+export type AccentPhrase = {
+  kana: string;
+  moras: number;
+  accent: number;
+  pause: boolean;
+};
+
+export type AccentScore = {
+  reference: AccentPhrase[];
+  openjtalk: AccentPhrase[];
+  jpreprocess: AccentPhrase[];
+  openjtalkCorrect: number;
+  jpreprocessCorrect: number;
+  openjtalkBoundaryF1: number;
+  jpreprocessBoundaryF1: number;
+};
+
+export type RubyMismatch = {
+  base: string;
+  ruby: string;
+  openjtalk: string;
+  jpreprocess: string;
+};
+
+export type RubyScore = {
+  spans: number;
+  openjtalkCorrect: number;
+  jpreprocessCorrect: number;
+  mismatches?: RubyMismatch[];
+};
+
+export type EditCounts = {
+  substitutions: number;
+  insertions: number;
+  deletions: number;
+};
+
+export type EditDistance = {
+  phonemes: number;
+  moras: number;
+  phonemeEdits: EditCounts;
+  moraEdits: EditCounts;
+  phonemeErrorRate: number;
+  moraErrorRate: number;
+};
+
+export type EngineGoldScore = {
+  phonemes: EditCounts;
+  moras: EditCounts;
+  exact: boolean;
+};
+
+export type GoldScore = {
+  reference: string[];
+  phonemes: number;
+  moras: number;
+  openjtalk: EngineGoldScore;
+  jpreprocess: EngineGoldScore;
+};
+
+export type PhrasePitch = AccentPhrase & {
+  high: boolean[];
+};
+
+export type PitchComparison = {
+  openjtalk: PhrasePitch[];
+  jpreprocess: PhrasePitch[];
+};
+
+export type PauseComparison = {
+  openjtalkPauses: number[];
+  jpreprocessPauses: number[];
+  openjtalkBreathGroups: number[];
+  jpreprocessBreathGroups: number[];
+};
+
+export type UtteranceFeatures = {
+  breathGroups: number;
+  accentPhrases: number;
+  moras: number;
+  // 1-based accent phrases marked as interrogative.
+  interrogative: number[];
+};
+
+export type Cause =
+  | "dictionary_reading"
+  | "segmentation"
+  | "digit"
+  | "accent_phrase"
+  | "unvoiced_vowel"
+  | "long_vowel"
+  | "label_generation"
+  | "unknown";
+
+export type WordTags = {
+  pos: string;
+  ctype: string;
+  cform: string;
+};
+
+export type WordDiff = {
+  kana: string;
+  openjtalk: WordTags;
+  jpreprocess: WordTags;
+};
+
+export type UtteranceComparison = {
+  openjtalk: UtteranceFeatures;
+  jpreprocess: UtteranceFeatures;
+};
+
 export type MatchEntry = {
   kind: "match";
   index: number;
   original: string;
   openjtalk: Phoneme[];
   jpreprocess: Phoneme[];
+  edits?: EditDistance;
+  segmentation?: Segmentation;
+  reading?: ReadingScore;
+  accent?: AccentScore;
+  ruby?: RubyScore;
+  gold?: GoldScore;
+};
+
+// A run of consecutive labels with the same differing field values.
+export type LabelFieldDiff = {
+  openjtalkIndex: number;
+  jpreprocessIndex: number;
+  labels?: number;
+  phonemes?: string;
+  // results.json files written before runs were collapsed have one label per diff.
+  phoneme?: string;
+  field: string;
+  openjtalk: string;
+  jpreprocess: string;
+};
+
+export type NjdNode = {
+  string: string;
+  pos: string;
+  ctype: string;
+  cform: string;
+  orig: string;
+  read: string;
+  pron: string;
+  acc: number;
+  moraSize: number;
+  chainRule: string;
+  chainFlag: number;
+};
+
+export type NjdNodePair = {
+  openjtalk?: NjdNode;
+  jpreprocess?: NjdNode;
+  equal: boolean;
+};
+
+export type Stage =
+  | "mecab2njd"
+  | "set_pronunciation"
+  | "set_digit"
+  | "set_accent_phrase"
+  | "set_accent_type"
+  | "set_unvoiced_vowel"
+  | "set_long_vowel"
+  | "njd2jpcommon";
+
+export type StageDivergence = {
+  stage: Stage;
+  nodeIndex?: number;
+  openjtalk?: NjdNode;
+  jpreprocess?: NjdNode;
 };
 
 export type MismatchEntry = {
-  kind: "light" | "fatal";
+  kind: "light" | "fatal" | "label_mismatch" | "accent_mismatch";
   index: number;
   original: string;
   openjtalk: Phoneme[];
   jpreprocess: Phoneme[];
+  alignment?: AlignedPhoneme[];
+  moras?: MoraSegment[];
+  edits?: EditDistance;
   lengthMismatch?: boolean;
+  cause?: Cause;
+  pitch?: PitchComparison;
+  pauses?: PauseComparison;
+  utterance?: UtteranceComparison;
+  words?: WordDiff[];
+  labelDiffs?: LabelFieldDiff[];
+  divergence?: StageDivergence;
+  njd?: NjdNodePair[];
+  segmentation?: Segmentation;
+  reading?: ReadingScore;
+  accent?: AccentScore;
+  ruby?: RubyScore;
+  gold?: GoldScore;
 };
 
 export type ErrorEntry = {
@@ -44,7 +306,23 @@ export type ErrorEntry = {
   jpreprocessError?: string;
 };
 
-export type Entry = MatchEntry | MismatchEntry | ErrorEntry;
+export type ExpectedEntry = {
+  kind: "expected";
+  index: number;
+  original: string;
+  note?: string;
+  actual: Entry;
+};
+
+export type Entry = MatchEntry | MismatchEntry | ErrorEntry | ExpectedEntry;
+
+export type Expectation = {
+  text?: string;
+  hash?: string;
+  kind: Entry["kind"];
+  phonemes?: string[];
+  note?: string;
+};
 
 export type FileResult = {
   file: string;
@@ -52,9 +330,96 @@ export type FileResult = {
   entries: Entry[];
 };
 
+export type IssueExample = {
+  file: string;
+  index: number;
+  original: string;
+};
+
+// A cluster of light or fatal mismatches with the same diff pattern in the same word.
+export type Issue = {
+  // Space-separated phonemes, "-" if the engine has nothing there.
+  openjtalk: string;
+  jpreprocess: string;
+  word: string;
+  diff: "light" | "fatal";
+  count: number;
+  examples: IssueExample[];
+};
+
+export type ReadingPair = {
+  // NJD pronunciations; empty if the engine has no node there.
+  openjtalk: string;
+  jpreprocess: string;
+  count: number;
+};
+
+// A word pronounced differently in fatal mismatches.
+export type OffendingWord = {
+  word: string;
+  count: number;
+  readings: ReadingPair[];
+  files: string[];
+};
+
 export type Results = {
   generatedAt: string;
   commit: string;
   totals: Stats;
   files: FileResult[];
+  staleExpectations?: Expectation[];
+  issues?: Issue[];
+  offendingWords?: OffendingWord[];
+};
+
+export type KindChange = {
+  original: string;
+  baselineIndex: number;
+  index: number;
+  baselineKind: Entry["kind"];
+  kind: Entry["kind"];
+};
+
+export type StatsDelta = {
+  total: number;
+  matches: number;
+  lightMismatches: number;
+  fatalMismatches: number;
+  labelMismatches: number;
+  accentMismatches?: number;
+  expected: number;
+  segmentationMismatches: number;
+  segmentationF1: number;
+  pauseMismatches?: number;
+  utteranceMismatches?: number;
+  wordMismatches?: number;
+  phonemeErrorRate?: number;
+  moraErrorRate?: number;
+  jpErrors: number;
+  ojtErrors: number;
+};
+
+export type FileDiff = {
+  file: string;
+  stats: StatsDelta;
+  fixed: KindChange[];
+  broken: KindChange[];
+  changed: KindChange[];
+  added?: UnpairedSentence[];
+  removed?: UnpairedSentence[];
+};
+
+export type UnpairedSentence = {
+  original: string;
+  index: number;
+  kind: string;
+};
+
+export type ResultsDiff = {
+  baselineCommit: string;
+  commit: string;
+  totals: StatsDelta;
+  files: FileDiff[];
+  addedFiles?: string[];
+  removedFiles?: string[];
 };
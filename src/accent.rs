@@ -0,0 +1,215 @@
+// Accent phrases, compared against the ones saved in a VOICEVOX project.
+use crate::label_groups;
+use crate::mora;
+use crate::segmentation::{self, BoundaryCounts};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AccentPhrase {
+    pub kana: String,
+    pub moras: usize,
+    // 1-based mora the pitch falls after. A flat (heiban) phrase has its mora count, as in the
+    // labels and VOICEVOX projects.
+    pub accent: usize,
+    // Whether a pause follows the phrase.
+    pub pause: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccentScore {
+    pub reference: Vec<AccentPhrase>,
+    pub openjtalk: Vec<AccentPhrase>,
+    pub jpreprocess: Vec<AccentPhrase>,
+    // Reference phrases reproduced with the same span and accent position.
+    pub openjtalk_correct: usize,
+    pub jpreprocess_correct: usize,
+    pub openjtalk_boundary_f1: f64,
+    pub jpreprocess_boundary_f1: f64,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct AccentCounts {
+    pub sentences: usize,
+    pub phrases: usize,
+    pub openjtalk_correct: usize,
+    pub jpreprocess_correct: usize,
+    pub openjtalk_boundaries: BoundaryCounts,
+    pub jpreprocess_boundaries: BoundaryCounts,
+}
+
+impl AccentCounts {
+    pub fn openjtalk_accuracy(&self) -> f64 {
+        crate::ratio(self.openjtalk_correct, self.phrases)
+    }
+
+    pub fn jpreprocess_accuracy(&self) -> f64 {
+        crate::ratio(self.jpreprocess_correct, self.phrases)
+    }
+
+    pub fn add_score(&mut self, score: &AccentScore) {
+        self.sentences += 1;
+        self.phrases += score.reference.len();
+        self.openjtalk_correct += score.openjtalk_correct;
+        self.jpreprocess_correct += score.jpreprocess_correct;
+        self.openjtalk_boundaries
+            .add(boundary_counts(&score.reference, &score.openjtalk));
+        self.jpreprocess_boundaries
+            .add(boundary_counts(&score.reference, &score.jpreprocess));
+    }
+
+    pub fn add(&mut self, other: AccentCounts) {
+        self.sentences += other.sentences;
+        self.phrases += other.phrases;
+        self.openjtalk_correct += other.openjtalk_correct;
+        self.jpreprocess_correct += other.jpreprocess_correct;
+        self.openjtalk_boundaries.add(other.openjtalk_boundaries);
+        self.jpreprocess_boundaries
+            .add(other.jpreprocess_boundaries);
+    }
+}
+
+// Accent phrase boundaries, as mora offsets.
+fn boundary_counts(reference: &[AccentPhrase], predicted: &[AccentPhrase]) -> BoundaryCounts {
+    let boundaries =
+        |phrases: &[AccentPhrase]| segmentation::boundaries(phrases.iter().map(|p| p.moras));
+    BoundaryCounts::new(&boundaries(reference), &boundaries(predicted))
+}
+
+// (start, end, accent) of each phrase, in moras.
+fn spans(phrases: &[AccentPhrase]) -> BTreeSet<(usize, usize, usize)> {
+    let mut offset = 0;
+    let mut spans = BTreeSet::new();
+    for phrase in phrases {
+        spans.insert((offset, offset + phrase.moras, phrase.accent));
+        offset += phrase.moras;
+    }
+    spans
+}
+
+// Accent phrases as the labels describe them.
+pub fn from_labels(labels: &[jlabel::Label]) -> Vec<AccentPhrase> {
+    label_groups::group_by(labels, label_groups::accent_phrase_key)
+        .into_iter()
+        .filter_map(|(_, range)| {
+            let accent_phrase = labels[range.start].accent_phrase_curr.as_ref()?;
+            let phonemes = label_groups::phonemes(&labels[range.clone()]);
+            Some(AccentPhrase {
+                kana: mora::moras_to_kana(&mora::phonemes_to_moras(&phonemes)),
+                moras: accent_phrase.mora_count as usize,
+                accent: accent_phrase.accent_position as usize,
+                pause: labels
+                    .get(range.end)
+                    .is_some_and(|label| label.phoneme.c.as_deref() == Some("pau")),
+            })
+        })
+        .collect()
+}
+
+pub fn score(
+    reference: &[AccentPhrase],
+    ojt_labels: &[jlabel::Label],
+    jp_labels: &[jlabel::Label],
+) -> AccentScore {
+    let openjtalk = from_labels(ojt_labels);
+    let jpreprocess = from_labels(jp_labels);
+    let reference_spans = spans(reference);
+    let correct = |phrases: &[AccentPhrase]| spans(phrases).intersection(&reference_spans).count();
+    AccentScore {
+        openjtalk_correct: correct(&openjtalk),
+        jpreprocess_correct: correct(&jpreprocess),
+        openjtalk_boundary_f1: boundary_counts(reference, &openjtalk).f1(),
+        jpreprocess_boundary_f1: boundary_counts(reference, &jpreprocess).f1(),
+        reference: reference.to_vec(),
+        openjtalk,
+        jpreprocess,
+    }
+}
+
+// Phrases separated by "/" ("、" where a pause follows), with "'" after the accent nucleus.
+pub fn format_phrases(phrases: &[AccentPhrase]) -> String {
+    let mut text = String::new();
+    for (i, phrase) in phrases.iter().enumerate() {
+        if i > 0 {
+            text.push(if phrases[i - 1].pause { '、' } else { '/' });
+        }
+        let moras = mora::kana_to_moras(&phrase.kana);
+        for (j, kana) in moras.iter().enumerate() {
+            text.push_str(&mora::moras_to_kana(std::slice::from_ref(kana)));
+            if j + 1 == phrase.accent {
+                text.push('\'');
+            }
+        }
+    }
+    text
+}
+
+// The inverse of `format_phrases`, for accents written by hand. A phrase without "'" is flat.
+pub fn parse_phrases(notation: &str) -> Vec<AccentPhrase> {
+    let mut phrases = vec![];
+    let mut kana = String::new();
+    let mut accent = None;
+    let mut chars = notation.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => accent = Some(mora::kana_to_moras(&kana).len()),
+            '/' | '、' => {}
+            _ => kana.push(c),
+        }
+        if matches!(c, '/' | '、') || chars.peek().is_none() {
+            let moras = mora::kana_to_moras(&std::mem::take(&mut kana));
+            phrases.push(AccentPhrase {
+                kana: mora::moras_to_kana(&moras),
+                moras: moras.len(),
+                accent: accent.take().unwrap_or(moras.len()),
+                pause: c == '、',
+            });
+        }
+    }
+    phrases
+}
+
+pub fn format_accent(score: &AccentScore) -> String {
+    format!(
+        "{} (OpenJTalk {} {}/{}, JPreprocess {} {}/{})",
+        format_phrases(&score.reference),
+        format_phrases(&score.openjtalk),
+        score.openjtalk_correct,
+        score.reference.len(),
+        format_phrases(&score.jpreprocess),
+        score.jpreprocess_correct,
+        score.reference.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrases(notation: &str) -> Vec<(String, usize, usize, bool)> {
+        parse_phrases(notation)
+            .into_iter()
+            .map(|p| (p.kana, p.moras, p.accent, p.pause))
+            .collect()
+    }
+
+    #[test]
+    fn parse_phrases_reads_accent_and_pauses() {
+        assert_eq!(
+            phrases("キョ'ーワ、イ'イ/テンキデス"),
+            [
+                ("キョオワ".to_string(), 3, 1, true),
+                ("イイ".to_string(), 2, 1, false),
+                ("テンキデス".to_string(), 5, 5, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_phrases_round_trips_format_phrases() {
+        let notation = "ア'メガ、フ'ル";
+        assert_eq!(format_phrases(&parse_phrases(notation)), notation);
+    }
+}
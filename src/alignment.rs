@@ -0,0 +1,476 @@
+// Side-by-side alignment of the engines' phonemes. Differences that are known not to matter
+// much (devoicing, a dropped pause, ...) are described by equivalence rules, so that they are
+// reported with their own severity and the name of the rule instead of as plain substitutions.
+use crate::edit::EditCounts;
+use crate::{DiffKind, Phoneme};
+use serde::{Deserialize, Serialize};
+
+// One column of the alignment. A deletion only has the OpenJTalk phoneme, an insertion only
+// the jpreprocess one.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlignedPhoneme {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openjtalk: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jpreprocess: Option<String>,
+    pub op: AlignOp,
+    pub diff: DiffKind,
+    // The equivalence rule behind an `Equivalent` column.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum AlignOp {
+    Equal,
+    // Part of a pair of phoneme sequences an equivalence rule matched.
+    Equivalent,
+    Substitute,
+    Delete,
+    Insert,
+}
+
+// An entry of the --equivalences file.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuleConfig {
+    name: String,
+    // "light" or "fatal".
+    severity: DiffKind,
+    // Each class lists phoneme sequences (space-separated, "" for nothing) that OpenJTalk and
+    // jpreprocess may use interchangeably.
+    classes: Vec<Vec<String>>,
+}
+
+struct Rule {
+    name: String,
+    severity: DiffKind,
+    // Every ordered pair of distinct sequences of a class: (OpenJTalk's, jpreprocess's).
+    pairs: Vec<(Vec<String>, Vec<String>)>,
+}
+
+pub struct Rules {
+    rules: Vec<Rule>,
+    // How many phonemes of a common prefix or suffix a rule could need to match.
+    context: usize,
+}
+
+impl Rules {
+    fn new(configs: Vec<RuleConfig>) -> Self {
+        let mut rules = Self {
+            rules: vec![],
+            context: 0,
+        };
+        for config in configs {
+            rules.push(config);
+        }
+        rules
+    }
+
+    fn push(&mut self, config: RuleConfig) {
+        let mut pairs = vec![];
+        for class in &config.classes {
+            let sequences = class
+                .iter()
+                .map(|s| s.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            for a in &sequences {
+                for b in sequences.iter().filter(|b| *b != a) {
+                    pairs.push((a.clone(), b.clone()));
+                }
+            }
+        }
+        self.context = pairs
+            .iter()
+            .map(|(a, b)| a.len().max(b.len()))
+            .fold(self.context, usize::max);
+        self.rules.push(Rule {
+            name: config.name,
+            severity: config.severity,
+            pairs,
+        });
+    }
+
+    // --ignore-pauses: a pause or silence that only one engine has is no difference at all.
+    pub fn ignore_pauses(&mut self) {
+        self.push(RuleConfig {
+            name: "pause".to_string(),
+            severity: DiffKind::None,
+            classes: vec![
+                vec!["pau".to_string(), String::new()],
+                vec!["sil".to_string(), String::new()],
+            ],
+        });
+        // Ties go to the first rule, so this one wins over any pause rule of --equivalences.
+        let rule = self.rules.pop().unwrap();
+        self.rules.insert(0, rule);
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        let configs: Vec<RuleConfig> =
+            serde_json::from_str(&json).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        for config in &configs {
+            anyhow::ensure!(
+                !matches!(config.severity, DiffKind::None),
+                "{}: the severity of {} must be light or fatal",
+                path,
+                config.name
+            );
+            anyhow::ensure!(
+                config.classes.iter().all(|class| class.len() >= 2),
+                "{}: every class of {} needs at least two sequences",
+                path,
+                config.name
+            );
+        }
+        Ok(Self::new(configs))
+    }
+}
+
+// Without --equivalences: a devoiced vowel or a lowercase n is a light difference, as it
+// always has been.
+impl Default for Rules {
+    fn default() -> Self {
+        let class = |a: &str, b: &str| vec![a.to_string(), b.to_string()];
+        Self::new(vec![
+            RuleConfig {
+                name: "devoicing".to_string(),
+                severity: DiffKind::Light,
+                classes: vec![
+                    class("a", "A"),
+                    class("i", "I"),
+                    class("u", "U"),
+                    class("e", "E"),
+                    class("o", "O"),
+                ],
+            },
+            RuleConfig {
+                name: "moraic-n".to_string(),
+                severity: DiffKind::Light,
+                classes: vec![class("N", "n")],
+            },
+        ])
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    End,
+    Equal,
+    // Indices into `Rules::rules` and the rule's `pairs`.
+    Rule(usize, usize),
+    Substitute,
+    Delete,
+    Insert,
+}
+
+impl Rules {
+    // Minimum cost alignment: a pair of sequences matched by a rule costs half as much as an
+    // edit, so that rules win over edits but not over equal phonemes. Ties go to equal
+    // phonemes, then rules, then substitutions.
+    fn align_span(&self, ojt: &[String], jp: &[String], alignment: &mut Vec<AlignedPhoneme>) {
+        let (n, m) = (ojt.len(), jp.len());
+        // best[i][j]: the cost of aligning ojt[i..] with jp[j..], and the first step to take.
+        let mut best = vec![vec![(0usize, Step::End); m + 1]; n + 1];
+        for i in (0..=n).rev() {
+            for j in (0..=m).rev() {
+                if (i, j) == (n, m) {
+                    continue;
+                }
+                let mut candidates = vec![];
+                if i < n && j < m && ojt[i] == jp[j] {
+                    candidates.push((best[i + 1][j + 1].0, Step::Equal));
+                }
+                for (rule_i, rule) in self.rules.iter().enumerate() {
+                    for (pair_i, (a, b)) in rule.pairs.iter().enumerate() {
+                        if ojt[i..].starts_with(a) && jp[j..].starts_with(b) {
+                            candidates.push((
+                                best[i + a.len()][j + b.len()].0 + 1,
+                                Step::Rule(rule_i, pair_i),
+                            ));
+                        }
+                    }
+                }
+                if i < n && j < m {
+                    candidates.push((best[i + 1][j + 1].0 + 2, Step::Substitute));
+                }
+                if i < n {
+                    candidates.push((best[i + 1][j].0 + 2, Step::Delete));
+                }
+                if j < m {
+                    candidates.push((best[i][j + 1].0 + 2, Step::Insert));
+                }
+                // The first of equally cheap candidates.
+                best[i][j] = candidates.into_iter().min_by_key(|c| c.0).unwrap();
+            }
+        }
+
+        let column =
+            |openjtalk: Option<&String>, jpreprocess: Option<&String>, op, diff| AlignedPhoneme {
+                openjtalk: openjtalk.cloned(),
+                jpreprocess: jpreprocess.cloned(),
+                op,
+                diff,
+                rule: None,
+            };
+        let (mut i, mut j) = (0, 0);
+        loop {
+            match best[i][j].1 {
+                Step::End => break,
+                Step::Equal => {
+                    alignment.push(column(
+                        ojt.get(i),
+                        jp.get(j),
+                        AlignOp::Equal,
+                        DiffKind::None,
+                    ));
+                    (i, j) = (i + 1, j + 1);
+                }
+                Step::Rule(rule_i, pair_i) => {
+                    let rule = &self.rules[rule_i];
+                    let (a, b) = &rule.pairs[pair_i];
+                    // Pair up the two sequences column by column; the longer one's rest
+                    // goes unpaired.
+                    for k in 0..a.len().max(b.len()) {
+                        let mut aligned =
+                            column(a.get(k), b.get(k), AlignOp::Equivalent, rule.severity);
+                        aligned.rule = Some(rule.name.clone());
+                        alignment.push(aligned);
+                    }
+                    (i, j) = (i + a.len(), j + b.len());
+                }
+                Step::Substitute => {
+                    alignment.push(column(
+                        ojt.get(i),
+                        jp.get(j),
+                        AlignOp::Substitute,
+                        DiffKind::Fatal,
+                    ));
+                    (i, j) = (i + 1, j + 1);
+                }
+                Step::Delete => {
+                    alignment.push(column(ojt.get(i), None, AlignOp::Delete, DiffKind::Fatal));
+                    i += 1;
+                }
+                Step::Insert => {
+                    alignment.push(column(None, jp.get(j), AlignOp::Insert, DiffKind::Fatal));
+                    j += 1;
+                }
+            }
+        }
+    }
+
+    // Only the part between the common prefix and suffix needs the (quadratic) alignment, plus
+    // enough of them for a rule to match across the edge.
+    pub fn align(&self, ojt_phonemes: &[String], jp_phonemes: &[String]) -> Vec<AlignedPhoneme> {
+        let prefix = ojt_phonemes
+            .iter()
+            .zip(jp_phonemes)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = ojt_phonemes[prefix..]
+            .iter()
+            .rev()
+            .zip(jp_phonemes[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let prefix = prefix.saturating_sub(self.context);
+        let suffix = suffix.saturating_sub(self.context);
+
+        let equal = |phoneme: &String| AlignedPhoneme {
+            openjtalk: Some(phoneme.clone()),
+            jpreprocess: Some(phoneme.clone()),
+            op: AlignOp::Equal,
+            diff: DiffKind::None,
+            rule: None,
+        };
+        let mut alignment = ojt_phonemes[..prefix].iter().map(equal).collect();
+        self.align_span(
+            &ojt_phonemes[prefix..ojt_phonemes.len() - suffix],
+            &jp_phonemes[prefix..jp_phonemes.len() - suffix],
+            &mut alignment,
+        );
+        alignment.extend(
+            ojt_phonemes[ojt_phonemes.len() - suffix..]
+                .iter()
+                .map(equal),
+        );
+        alignment
+    }
+}
+
+// Each engine's phonemes on their own (`openjtalk`/`jpreprocess` in the JSON), annotated from
+// the alignment.
+pub fn phonemes_with_diff(alignment: &[AlignedPhoneme]) -> (Vec<Phoneme>, Vec<Phoneme>) {
+    let side = |value: fn(&AlignedPhoneme) -> &Option<String>| {
+        alignment
+            .iter()
+            .filter_map(|aligned| {
+                value(aligned).as_ref().map(|value| Phoneme {
+                    value: value.clone(),
+                    diff: aligned.diff,
+                    rule: aligned.rule.clone(),
+                })
+            })
+            .collect()
+    };
+    (side(|a| &a.openjtalk), side(|a| &a.jpreprocess))
+}
+
+// The edits turning OpenJTalk's phonemes into jpreprocess's. Light differences are not edits.
+pub fn alignment_edits(alignment: &[AlignedPhoneme]) -> EditCounts {
+    let mut edits = EditCounts::default();
+    for aligned in alignment
+        .iter()
+        .filter(|aligned| matches!(aligned.diff, DiffKind::Fatal))
+    {
+        match (&aligned.openjtalk, &aligned.jpreprocess) {
+            (Some(_), Some(_)) => edits.substitutions += 1,
+            (Some(_), None) => edits.deletions += 1,
+            (None, _) => edits.insertions += 1,
+        }
+    }
+    edits
+}
+
+// The alignment as two lines with the columns lined up and `-` for gaps.
+pub fn format_alignment(alignment: &[AlignedPhoneme]) -> (String, String) {
+    let mut ojt_columns = vec![];
+    let mut jp_columns = vec![];
+    for aligned in alignment {
+        let ojt = aligned.openjtalk.as_deref().unwrap_or("-");
+        let jp = aligned.jpreprocess.as_deref().unwrap_or("-");
+        let width = ojt.len().max(jp.len());
+        let color = match aligned.diff {
+            DiffKind::None => "",
+            DiffKind::Light => "\x1b[33m",
+            DiffKind::Fatal => "\x1b[31m",
+        };
+        let reset = if color.is_empty() { "" } else { "\x1b[0m" };
+        ojt_columns.push(format!("{}{:<width$}{}", color, ojt, reset));
+        jp_columns.push(format!("{}{:<width$}{}", color, jp, reset));
+    }
+    (ojt_columns.join(" "), jp_columns.join(" "))
+}
+
+// The rules behind the equivalent columns, e.g. "devoicing, pause".
+pub fn format_rules(alignment: &[AlignedPhoneme]) -> String {
+    let mut names: Vec<&str> = vec![];
+    for rule in alignment
+        .iter()
+        .filter_map(|aligned| aligned.rule.as_deref())
+    {
+        if !names.contains(&rule) {
+            names.push(rule);
+        }
+    }
+    names.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phonemes(phonemes: &str) -> Vec<String> {
+        phonemes.split_whitespace().map(str::to_string).collect()
+    }
+
+    // Each column as "OpenJTalk/jpreprocess", with its severity and rule unless equal.
+    fn columns(alignment: &[AlignedPhoneme]) -> Vec<String> {
+        alignment
+            .iter()
+            .map(|aligned| {
+                let mut column = format!(
+                    "{}/{}",
+                    aligned.openjtalk.as_deref().unwrap_or("-"),
+                    aligned.jpreprocess.as_deref().unwrap_or("-")
+                );
+                match aligned.diff {
+                    DiffKind::None => {}
+                    DiffKind::Light => column += " light",
+                    DiffKind::Fatal => column += " fatal",
+                }
+                if let Some(rule) = &aligned.rule {
+                    column += &format!(" ({})", rule);
+                }
+                column
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_rules() {
+        let rules = Rules::default();
+        let alignment = rules.align(
+            &phonemes("sil k i t a N pau s a sil"),
+            &phonemes("sil k I t a n s o sil"),
+        );
+        assert_eq!(
+            columns(&alignment),
+            [
+                "sil/sil",
+                "k/k",
+                "i/I light (devoicing)",
+                "t/t",
+                "a/a",
+                "N/n light (moraic-n)",
+                "pau/- fatal",
+                "s/s",
+                "a/o fatal",
+                "sil/sil",
+            ]
+        );
+        let edits = alignment_edits(&alignment);
+        assert_eq!(
+            (edits.substitutions, edits.insertions, edits.deletions),
+            (1, 0, 1)
+        );
+    }
+
+    #[test]
+    fn ignore_pauses() {
+        let mut rules = Rules::default();
+        rules.ignore_pauses();
+        let alignment = rules.align(&phonemes("sil k a pau s a sil"), &phonemes("sil k a s a"));
+        assert_eq!(
+            columns(&alignment),
+            [
+                "sil/sil",
+                "k/k",
+                "a/a",
+                "pau/- (pause)",
+                "s/s",
+                "a/a",
+                "sil/- (pause)"
+            ]
+        );
+        assert!(alignment
+            .iter()
+            .all(|aligned| aligned.diff == DiffKind::None));
+    }
+
+    // The common prefix ends in the middle of the long vowel, so the rule only matches if the
+    // aligned span is widened into the prefix.
+    #[test]
+    fn rule_across_the_prefix_edge() {
+        let rules = Rules::new(vec![RuleConfig {
+            name: "long-vowel".to_string(),
+            severity: DiffKind::Light,
+            classes: vec![vec!["o o".to_string(), "o u".to_string()]],
+        }]);
+        let alignment = rules.align(&phonemes("sil k o o sil"), &phonemes("sil k o u sil"));
+        assert_eq!(
+            columns(&alignment),
+            [
+                "sil/sil",
+                "k/k",
+                "o/o light (long-vowel)",
+                "o/u light (long-vowel)",
+                "sil/sil",
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+// The likely cause of a light or fatal mismatch, from the first NJD stage at which the engines
+// disagree, so that fixes can be prioritized by what they would fix.
+use crate::segmentation::Segmentation;
+use crate::stages::{Stage, StageDivergence};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Cause {
+    // The same tokens, read differently by the dictionary (or its pronunciation rules).
+    DictionaryReading,
+    Segmentation,
+    // Numbers read differently (set_digit).
+    Digit,
+    // Accent phrases chained differently or given a different accent type, which the
+    // unvoiced vowel rule depends on.
+    AccentPhrase,
+    UnvoicedVowel,
+    LongVowel,
+    // The NJD agrees; the labels built from it do not.
+    LabelGeneration,
+    // The NJD snapshots could not be taken.
+    Unknown,
+}
+
+impl Cause {
+    pub fn name(self) -> &'static str {
+        match self {
+            Cause::DictionaryReading => "dictionary_reading",
+            Cause::Segmentation => "segmentation",
+            Cause::Digit => "digit",
+            Cause::AccentPhrase => "accent_phrase",
+            Cause::UnvoicedVowel => "unvoiced_vowel",
+            Cause::LongVowel => "long_vowel",
+            Cause::LabelGeneration => "label_generation",
+            Cause::Unknown => "unknown",
+        }
+    }
+}
+
+// MeCab's output feeds mecab2njd directly, so a divergence there is a segmentation difference
+// if the token boundaries differ and a dictionary difference otherwise.
+pub fn classify(
+    divergence: Option<&StageDivergence>,
+    segmentation: Option<&Segmentation>,
+) -> Cause {
+    let Some(divergence) = divergence else {
+        return Cause::Unknown;
+    };
+    match divergence.stage {
+        Stage::Mecab2njd if segmentation.is_some() => Cause::Segmentation,
+        Stage::Mecab2njd | Stage::SetPronunciation => Cause::DictionaryReading,
+        Stage::SetDigit => Cause::Digit,
+        Stage::SetAccentPhrase | Stage::SetAccentType => Cause::AccentPhrase,
+        Stage::SetUnvoicedVowel => Cause::UnvoicedVowel,
+        Stage::SetLongVowel => Cause::LongVowel,
+        Stage::Njd2jpcommon => Cause::LabelGeneration,
+    }
+}
+
+// ", causes: dictionary_reading 3, segmentation 1", or nothing without light or fatal mismatches.
+pub fn format_counts(counts: &BTreeMap<Cause, usize>) -> String {
+    if counts.is_empty() {
+        return String::new();
+    }
+    format!(
+        ", causes: {}",
+        counts
+            .iter()
+            .map(|(cause, count)| format!("{} {}", cause.name(), count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
@@ -0,0 +1,542 @@
+// Corpus files. Besides plain text, some formats carry a human-checked reading, pronunciation,
+// accent phrases or ruby per sentence, which both engines are scored against.
+use crate::accent::{self, AccentPhrase};
+use crate::gold;
+use crate::mora;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub struct Sentence {
+    pub text: String,
+    // Reference reading in kana.
+    pub reading: Option<String>,
+    // Reference pronunciation in phonemes, from a gold file.
+    pub pronunciation: Option<Vec<String>>,
+    pub accent_phrases: Option<Vec<AccentPhrase>>,
+    pub ruby: Vec<Ruby>,
+}
+
+impl Sentence {
+    pub fn has_reference(&self) -> bool {
+        self.reading.is_some()
+            || self.pronunciation.is_some()
+            || self.accent_phrases.is_some()
+            || !self.ruby.is_empty()
+    }
+}
+
+// A ruby-annotated span of `Sentence::text`, in characters.
+pub struct Ruby {
+    pub start: usize,
+    pub end: usize,
+    pub reading: String,
+}
+
+// With `keep_sentence_end`, plain and Aozora Bunko text is also split after `？` and `！`, which
+// stay at the end of their sentence, so that both engines see a sentence that ends in a question
+// or exclamation as such. Other formats are one sentence per entry already.
+pub fn load(path: &Path, keep_sentence_end: bool) -> anyhow::Result<Vec<Sentence>> {
+    if path.extension().is_some_and(|e| e == "raw") {
+        let bytes = std::fs::read(path)?;
+        let (text, _, _) = encoding_rs::SHIFT_JIS.decode(&bytes);
+        return Ok(parse_aozora(&text, keep_sentence_end));
+    }
+    let text = std::fs::read_to_string(path)?;
+    let parsed = match path.extension().and_then(|e| e.to_str()) {
+        Some("vvproj") => Some(parse_vvproj(&text)),
+        Some("tsv") => Some(parse_gold_tsv(&text)),
+        Some("jsonl") => Some(parse_gold_jsonl(&text)),
+        _ => None,
+    };
+    if let Some(parsed) = parsed {
+        return parsed.map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e));
+    }
+    if is_rohan(&text) {
+        return parse_rohan(&text);
+    }
+    // An old copy of the transcript, from before data/rohan.rb kept the readings.
+    if path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("rohan4600"))
+    {
+        eprintln!(
+            "{}: no ROHAN4600 readings; run `ruby rohan.rb` in data/ to score against them",
+            path.display()
+        );
+    }
+    Ok(split_sentences(&text, keep_sentence_end)
+        .into_iter()
+        .map(|text| Sentence {
+            text,
+            reading: None,
+            pronunciation: None,
+            accent_phrases: None,
+            ruby: vec![],
+        })
+        .collect())
+}
+
+pub fn split_sentences(text: &str, keep_sentence_end: bool) -> Vec<String> {
+    let mut sentences = vec![];
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if matches!(c, '。' | '「' | '」') {
+            if !current.is_empty() {
+                sentences.push(std::mem::take(&mut current));
+            }
+        } else if !c.is_whitespace() {
+            current.push(c);
+            if keep_sentence_end && ends_sentence(c, chars.peek().copied()) {
+                sentences.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current);
+    }
+    sentences
+}
+
+// Whether `c` is a `？` or `！` (full or half width) that is not followed by another one, as in
+// "本当！？".
+fn ends_sentence(c: char, next: Option<char>) -> bool {
+    let is_mark = |c: char| matches!(c, '？' | '！' | '?' | '!');
+    is_mark(c) && !next.is_some_and(is_mark)
+}
+
+// The ROHAN4600 transcript as published, one sentence per line:
+// ROHAN4600_0001:流し斬りが完全に入れば、デバフの効果が付与される。,ナガシギリガ...
+fn is_rohan(text: &str) -> bool {
+    text.lines()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| line.starts_with("ROHAN4600_"))
+}
+
+fn parse_rohan(text: &str) -> anyhow::Result<Vec<Sentence>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (_, transcript) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow::anyhow!("malformed ROHAN4600 line: {}", line))?;
+            let (text, reading) = transcript
+                .split_once(',')
+                .ok_or_else(|| anyhow::anyhow!("ROHAN4600 line without reading: {}", line))?;
+            // Parenthesized parts are annotations, not something to read out.
+            let strip = |s: &str| {
+                let s = lazy_regex::regex_replace_all!(r"\(.+?\)", s, "");
+                lazy_regex::regex_replace_all!(r"\s+", &s, "").into_owned()
+            };
+            Ok(Sentence {
+                text: strip(text),
+                reading: Some(strip(reading)),
+                pronunciation: None,
+                accent_phrases: None,
+                ruby: vec![],
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VvprojTalk {
+    audio_keys: Vec<String>,
+    audio_items: HashMap<String, VvprojAudioItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VvprojAudioItem {
+    text: String,
+    query: Option<VvprojQuery>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VvprojQuery {
+    accent_phrases: Vec<VvprojAccentPhrase>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VvprojAccentPhrase {
+    moras: Vec<VvprojMora>,
+    accent: usize,
+    pause_mora: Option<VvprojMora>,
+}
+
+#[derive(Deserialize)]
+struct VvprojMora {
+    text: String,
+}
+
+// One sentence per audio item, in the order of the project. The accent phrases the user tuned
+// are the reference accent, and their moras the reference reading.
+fn parse_vvproj(json: &str) -> anyhow::Result<Vec<Sentence>> {
+    // Old projects have the talk fields at the top level.
+    let project: serde_json::Value = serde_json::from_str(json)?;
+    let talk = VvprojTalk::deserialize(project.get("talk").unwrap_or(&project))?;
+    talk.audio_keys
+        .iter()
+        .map(|key| {
+            let item = talk
+                .audio_items
+                .get(key)
+                .ok_or_else(|| anyhow::anyhow!("missing audio item {}", key))?;
+            let accent_phrases = item.query.as_ref().map(|query| {
+                query
+                    .accent_phrases
+                    .iter()
+                    .map(|phrase| {
+                        let kana = phrase
+                            .moras
+                            .iter()
+                            .map(|m| m.text.as_str())
+                            .collect::<String>();
+                        AccentPhrase {
+                            kana: mora::moras_to_kana(&mora::kana_to_moras(&kana)),
+                            moras: phrase.moras.len(),
+                            accent: phrase.accent,
+                            pause: phrase.pause_mora.is_some(),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            });
+            Ok(Sentence {
+                text: lazy_regex::regex_replace_all!(r"\s+", &item.text, "").into_owned(),
+                reading: accent_phrases
+                    .as_ref()
+                    .map(|phrases| phrases.iter().map(|p| p.kana.as_str()).collect()),
+                pronunciation: None,
+                accent_phrases,
+                ruby: vec![],
+            })
+        })
+        .collect()
+}
+
+// Which run of characters a ruby without "｜" applies to.
+#[derive(PartialEq)]
+enum Script {
+    Kanji,
+    Hiragana,
+    Katakana,
+    Alphanumeric,
+    Other,
+}
+
+fn script(c: char) -> Script {
+    match c {
+        '々'
+        | '〆'
+        | '〇'
+        | 'ヶ'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{20000}'..='\u{2ffff}' => Script::Kanji,
+        'ぁ'..='ゟ' => Script::Hiragana,
+        'ァ'..='ヿ' => Script::Katakana,
+        'a'..='z' | 'A'..='Z' | '0'..='9' | 'ａ'..='ｚ' | 'Ａ'..='Ｚ' | '０'..='９' => {
+            Script::Alphanumeric
+        }
+        _ => Script::Other,
+    }
+}
+
+// An Aozora Bunko text (the Shift_JIS `.raw` files): the same cleanup as data/preprocess.rb,
+// except that ruby is kept as the reading of its base text.
+fn parse_aozora(text: &str, keep_sentence_end: bool) -> Vec<Sentence> {
+    let text = text.replace("\r\n", "\n");
+    let text = lazy_regex::regex_replace_all!(r"［＃[０-９]+字下げ］.+", &text, "");
+    let text = lazy_regex::regex_replace_all!(r"［.+?］", &text, "");
+    let text = lazy_regex::regex_replace_all!(r"(?s).+-\n\n", &text, "");
+    let text = lazy_regex::regex_replace_all!(r"(?s)底本：.+", &text, "");
+
+    // Plain text, and the ruby spans in character offsets into it.
+    let mut plain: Vec<char> = vec![];
+    let mut spans: Vec<(usize, usize, String)> = vec![];
+    let mut base_start = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '｜' => base_start = Some(plain.len()),
+            '《' => {
+                let reading = chars
+                    .by_ref()
+                    .take_while(|&c| c != '》')
+                    .collect::<String>();
+                let start = base_start.take().unwrap_or_else(|| {
+                    let Some(last) = plain.last().map(|&c| script(c)) else {
+                        return 0;
+                    };
+                    plain.len()
+                        - plain
+                            .iter()
+                            .rev()
+                            .take_while(|&&c| script(c) == last)
+                            .count()
+                });
+                if start < plain.len() {
+                    spans.push((start, plain.len(), reading));
+                }
+            }
+            _ => {
+                // A "｜" without ruby marks nothing; it must not reach into the next sentence.
+                if matches!(c, '。' | '「' | '」' | '\n')
+                    || keep_sentence_end && ends_sentence(c, chars.clone().next())
+                {
+                    base_start = None;
+                }
+                plain.push(c);
+            }
+        }
+    }
+
+    // Split as `split_sentences` does, remembering where each kept character ended up.
+    let mut sentences: Vec<Sentence> = vec![];
+    let mut current = String::new();
+    let mut positions: Vec<Option<(usize, usize)>> = vec![None; plain.len()];
+    let end_sentence = |sentences: &mut Vec<Sentence>, current: &mut String| {
+        if !current.is_empty() {
+            sentences.push(Sentence {
+                text: std::mem::take(current),
+                reading: None,
+                pronunciation: None,
+                accent_phrases: None,
+                ruby: vec![],
+            });
+        }
+    };
+    for (i, &c) in plain.iter().enumerate() {
+        if matches!(c, '。' | '「' | '」') {
+            end_sentence(&mut sentences, &mut current);
+        } else if !c.is_whitespace() {
+            positions[i] = Some((sentences.len(), current.chars().count()));
+            current.push(c);
+            if keep_sentence_end && ends_sentence(c, plain.get(i + 1).copied()) {
+                end_sentence(&mut sentences, &mut current);
+            }
+        }
+    }
+    end_sentence(&mut sentences, &mut current);
+
+    // Ruby whose base crosses a sentence boundary is dropped.
+    for (start, end, reading) in spans {
+        let kept = positions[start..end].iter().flatten().collect::<Vec<_>>();
+        let (Some(&&(sentence, first)), Some(&&(last_sentence, last))) =
+            (kept.first(), kept.last())
+        else {
+            continue;
+        };
+        if sentence == last_sentence {
+            sentences[sentence].ruby.push(Ruby {
+                start: first,
+                end: last + 1,
+                reading,
+            });
+        }
+    }
+    sentences
+}
+
+fn gold_sentence(text: &str, pronunciation: &str, accent: Option<&str>) -> Sentence {
+    Sentence {
+        text: text.trim().to_string(),
+        reading: None,
+        pronunciation: Some(gold::parse_pronunciation(pronunciation)),
+        accent_phrases: accent
+            .filter(|a| !a.trim().is_empty())
+            .map(|a| accent::parse_phrases(a.trim())),
+        ruby: vec![],
+    }
+}
+
+// text<TAB>pronunciation[<TAB>accent], one sentence per line. Lines starting with "#" are
+// comments.
+fn parse_gold_tsv(text: &str) -> anyhow::Result<Vec<Sentence>> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let mut fields = line.split('\t');
+            let (Some(text), Some(pronunciation)) = (fields.next(), fields.next()) else {
+                anyhow::bail!(
+                    "line {}: expected text<TAB>pronunciation[<TAB>accent]",
+                    i + 1
+                );
+            };
+            Ok(gold_sentence(text, pronunciation, fields.next()))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct GoldRow {
+    text: String,
+    // Either phonemes or a katakana pronunciation.
+    phonemes: Option<Vec<String>>,
+    pronunciation: Option<String>,
+    accent: Option<String>,
+}
+
+fn parse_gold_jsonl(text: &str) -> anyhow::Result<Vec<Sentence>> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let row: GoldRow =
+                serde_json::from_str(line).map_err(|e| anyhow::anyhow!("line {}: {}", i + 1, e))?;
+            let pronunciation = match (row.phonemes, row.pronunciation) {
+                (Some(phonemes), _) => phonemes.join(" "),
+                (None, Some(pronunciation)) => pronunciation,
+                (None, None) => {
+                    anyhow::bail!("line {}: needs phonemes or a pronunciation", i + 1)
+                }
+            };
+            Ok(gold_sentence(
+                &row.text,
+                &pronunciation,
+                row.accent.as_deref(),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ruby(sentence: &Sentence) -> Vec<(usize, usize, &str)> {
+        sentence
+            .ruby
+            .iter()
+            .map(|r| (r.start, r.end, r.reading.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn aozora_ruby_applies_to_the_run_of_the_same_script() {
+        let sentences = parse_aozora("吾輩《わがはい》は猫である。名前はまだ無《な》い。", false);
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(sentences[0].text, "吾輩は猫である");
+        assert_eq!(ruby(&sentences[0]), [(0, 2, "わがはい")]);
+        assert_eq!(sentences[1].text, "名前はまだ無い");
+        assert_eq!(ruby(&sentences[1]), [(5, 6, "な")]);
+    }
+
+    #[test]
+    fn aozora_ruby_starts_at_the_bar() {
+        let sentences = parse_aozora("その｜三毛猫《みけねこ》が来た", false);
+        assert_eq!(sentences[0].text, "その三毛猫が来た");
+        assert_eq!(ruby(&sentences[0]), [(2, 5, "みけねこ")]);
+    }
+
+    #[test]
+    fn aozora_bar_without_ruby_ends_with_its_sentence() {
+        let sentences = parse_aozora("｜ここで終わる。次の文《ぶん》", false);
+        assert_eq!(sentences.len(), 2);
+        assert!(sentences[0].ruby.is_empty());
+        assert_eq!(ruby(&sentences[1]), [(2, 3, "ぶん")]);
+    }
+
+    #[test]
+    fn rohan_transcript_is_detected_by_its_first_line() {
+        assert!(is_rohan("\nROHAN4600_0001:文章,ブンショー\n"));
+        assert!(!is_rohan(
+            "流し斬りが完全に入れば、デバフの効果が付与される。\n"
+        ));
+    }
+
+    #[test]
+    fn rohan_annotations_are_stripped() {
+        let sentences =
+            parse_rohan("ROHAN4600_0001:今日(きょう)は晴れ,キョーワ(今日は) ハレ\n").unwrap();
+        assert_eq!(sentences[0].text, "今日は晴れ");
+        assert_eq!(sentences[0].reading.as_deref(), Some("キョーワハレ"));
+        assert!(parse_rohan("ROHAN4600_0001:読みのない文").is_err());
+    }
+
+    #[test]
+    fn vvproj_accent_phrases_are_the_reference() {
+        let project = r#"{"talk": {
+            "audioKeys": ["b", "a"],
+            "audioItems": {
+                "a": {"text": "こんにちは 世界", "query": {"accentPhrases": [
+                    {"moras": [{"text": "コ"}, {"text": "ン"}, {"text": "ニ"}, {"text": "チ"},
+                        {"text": "ワ"}], "accent": 5, "pauseMora": {"text": "、"}},
+                    {"moras": [{"text": "セ"}, {"text": "カ"}, {"text": "イ"}], "accent": 1,
+                        "pauseMora": null}
+                ]}},
+                "b": {"text": "無題"}
+            }
+        }}"#;
+        let sentences = parse_vvproj(project).unwrap();
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(sentences[0].text, "無題");
+        assert!(sentences[0].accent_phrases.is_none() && sentences[0].reading.is_none());
+        assert_eq!(sentences[1].text, "こんにちは世界");
+        assert_eq!(sentences[1].reading.as_deref(), Some("コンニチワセカイ"));
+        let phrases = sentences[1].accent_phrases.as_ref().unwrap();
+        let phrases = phrases
+            .iter()
+            .map(|p| (p.kana.as_str(), p.moras, p.accent, p.pause))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            phrases,
+            [("コンニチワ", 5, 5, true), ("セカイ", 3, 1, false)]
+        );
+    }
+
+    #[test]
+    fn vvproj_talk_fields_may_be_at_the_top_level() {
+        let project = r#"{"audioKeys": ["a"], "audioItems": {"a": {"text": "はい"}}}"#;
+        assert_eq!(parse_vvproj(project).unwrap()[0].text, "はい");
+        assert!(parse_vvproj(r#"{"audioKeys": ["a"], "audioItems": {}}"#).is_err());
+    }
+
+    fn strings(strings: &[&str]) -> Vec<String> {
+        strings.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn gold_tsv_takes_phonemes_or_kana_and_an_optional_accent() {
+        let tsv = "# text\tpronunciation\taccent\n\
+            はい\tsil h a i sil\n\
+            \n\
+            雨です\tアメデス\tア'メ/デス\n";
+        let sentences = parse_gold_tsv(tsv).unwrap();
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(
+            sentences[0].pronunciation,
+            Some(strings(&["sil", "h", "a", "i", "sil"]))
+        );
+        assert!(sentences[0].accent_phrases.is_none());
+        assert_eq!(sentences[1].text, "雨です");
+        assert_eq!(
+            sentences[1].pronunciation,
+            Some(strings(&["a", "m", "e", "d", "e", "s", "u"]))
+        );
+        assert_eq!(sentences[1].accent_phrases.as_ref().map(Vec::len), Some(2));
+        let error = parse_gold_tsv("はい\n").err().unwrap().to_string();
+        assert!(error.starts_with("line 1:"), "{}", error);
+    }
+
+    #[test]
+    fn gold_jsonl_prefers_phonemes_over_a_pronunciation() {
+        let jsonl = r#"{"text": "はい", "phonemes": ["h", "a", "i"], "pronunciation": "イイエ"}
+{"text": "いいえ", "pronunciation": "イイエ", "accent": "イ'イエ"}"#;
+        let sentences = parse_gold_jsonl(jsonl).unwrap();
+        assert_eq!(sentences[0].pronunciation, Some(strings(&["h", "a", "i"])));
+        assert_eq!(sentences[1].pronunciation, Some(strings(&["i", "i", "e"])));
+        assert!(sentences[1].accent_phrases.is_some());
+        let error = parse_gold_jsonl("\n{\"text\": \"はい\"}").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 2: needs phonemes or a pronunciation"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// Edit distance between a reference sequence and a hypothesis, from a Myers alignment.
+use crate::label_groups;
+use serde::{Deserialize, Serialize};
+use similar::DiffOp;
+use std::hash::Hash;
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct EditCounts {
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl EditCounts {
+    // Within a replaced block, paired-up items count as substitutions and the rest of the
+    // longer side as insertions or deletions.
+    pub fn new<T: Hash + Ord>(reference: &[T], hypothesis: &[T]) -> Self {
+        use similar::{capture_diff_slices, Algorithm};
+
+        let mut counts = Self::default();
+        for op in capture_diff_slices(Algorithm::Myers, reference, hypothesis) {
+            counts.add_op(&op);
+        }
+        counts
+    }
+
+    fn add_op(&mut self, op: &DiffOp) {
+        match *op {
+            DiffOp::Equal { .. } => {}
+            DiffOp::Delete { old_len, .. } => self.deletions += old_len,
+            DiffOp::Insert { new_len, .. } => self.insertions += new_len,
+            DiffOp::Replace {
+                old_len, new_len, ..
+            } => {
+                self.substitutions += old_len.min(new_len);
+                self.deletions += old_len.saturating_sub(new_len);
+                self.insertions += new_len.saturating_sub(old_len);
+            }
+        }
+    }
+
+    pub fn add(&mut self, other: EditCounts) {
+        self.substitutions += other.substitutions;
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+    }
+
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.insertions + self.deletions
+    }
+}
+
+// Errors per reference item; 0 for an empty reference.
+pub fn error_rate(errors: usize, reference: usize) -> f64 {
+    if reference == 0 {
+        0.0
+    } else {
+        errors as f64 / reference as f64
+    }
+}
+
+// How far jpreprocess's pronunciation of a sentence is from OpenJTalk's, taking OpenJTalk's as
+// the reference. Phoneme edits come from `alignment_edits`, so light differences such as
+// devoicing are not counted; moras are taken from the labels, as in `mora_diff`.
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct EditDistance {
+    pub phonemes: usize,
+    pub moras: usize,
+    pub phoneme_edits: EditCounts,
+    pub mora_edits: EditCounts,
+    pub phoneme_error_rate: f64,
+    pub mora_error_rate: f64,
+}
+
+impl EditDistance {
+    pub fn new(
+        ojt_labels: &[jlabel::Label],
+        jp_labels: &[jlabel::Label],
+        phoneme_edits: EditCounts,
+    ) -> Self {
+        let ojt_phonemes = label_groups::phonemes(ojt_labels);
+        let ojt_moras = label_groups::moras(ojt_labels);
+        let jp_moras = label_groups::moras(jp_labels);
+        let mora_edits = EditCounts::new(&ojt_moras, &jp_moras);
+        Self {
+            phonemes: ojt_phonemes.len(),
+            moras: ojt_moras.len(),
+            phoneme_edits,
+            mora_edits,
+            phoneme_error_rate: error_rate(phoneme_edits.errors(), ojt_phonemes.len()),
+            mora_error_rate: error_rate(mora_edits.errors(), ojt_moras.len()),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct EditDistanceCounts {
+    pub phonemes: usize,
+    pub moras: usize,
+    pub phoneme_edits: EditCounts,
+    pub mora_edits: EditCounts,
+}
+
+// The `Stats` fields for the edit distance between the engines.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EditStats {
+    pub phonemes: usize,
+    pub phoneme_substitutions: usize,
+    pub phoneme_insertions: usize,
+    pub phoneme_deletions: usize,
+    pub phoneme_error_rate: f64,
+    pub moras: usize,
+    pub mora_substitutions: usize,
+    pub mora_insertions: usize,
+    pub mora_deletions: usize,
+    pub mora_error_rate: f64,
+}
+
+impl EditDistanceCounts {
+    pub fn add_distance(&mut self, distance: &EditDistance) {
+        self.phonemes += distance.phonemes;
+        self.moras += distance.moras;
+        self.phoneme_edits.add(distance.phoneme_edits);
+        self.mora_edits.add(distance.mora_edits);
+    }
+
+    pub fn add(&mut self, other: EditDistanceCounts) {
+        self.phonemes += other.phonemes;
+        self.moras += other.moras;
+        self.phoneme_edits.add(other.phoneme_edits);
+        self.mora_edits.add(other.mora_edits);
+    }
+
+    pub fn phoneme_error_rate(&self) -> f64 {
+        error_rate(self.phoneme_edits.errors(), self.phonemes)
+    }
+
+    pub fn mora_error_rate(&self) -> f64 {
+        error_rate(self.mora_edits.errors(), self.moras)
+    }
+
+    pub fn stats(&self) -> EditStats {
+        EditStats {
+            phonemes: self.phonemes,
+            phoneme_substitutions: self.phoneme_edits.substitutions,
+            phoneme_insertions: self.phoneme_edits.insertions,
+            phoneme_deletions: self.phoneme_edits.deletions,
+            phoneme_error_rate: self.phoneme_error_rate(),
+            moras: self.moras,
+            mora_substitutions: self.mora_edits.substitutions,
+            mora_insertions: self.mora_edits.insertions,
+            mora_deletions: self.mora_edits.deletions,
+            mora_error_rate: self.mora_error_rate(),
+        }
+    }
+}
+
+pub fn format_edits(edits: &EditCounts) -> String {
+    format!(
+        "{} sub / {} ins / {} del",
+        edits.substitutions, edits.insertions, edits.deletions
+    )
+}
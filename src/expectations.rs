@@ -0,0 +1,174 @@
+// Known differences: sentences whose mismatch or error is intentional, or a known OpenJTalk bug.
+// Such sentences are reported as `expected` instead of counting as mismatches or errors.
+use crate::{Entry, ExpectedEntry};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Expectation {
+    // The sentence, or `sentence_hash` of it; one of the two is required.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    // The entry kind the sentence is expected to have, e.g. "fatal" or "jp_error".
+    pub kind: String,
+    // If given, jpreprocess must also produce exactly these phonemes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phonemes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+// FNV-1a (64 bit) of the sentence, as 16 hex digits.
+pub fn sentence_hash(sentence: &str) -> String {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in sentence.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// The "Original:" line of a mismatch or error report, with the hash to list it by.
+pub fn format_original(sentence: &str) -> String {
+    format!(
+        "     Original: {} \x1b[90m(hash {})\x1b[0m",
+        sentence,
+        sentence_hash(sentence)
+    )
+}
+
+fn jpreprocess_phonemes(entry: &Entry) -> Option<Vec<&str>> {
+    let phonemes = match entry {
+        Entry::Match(entry) => &entry.jpreprocess,
+        Entry::Light(entry)
+        | Entry::Fatal(entry)
+        | Entry::LabelMismatch(entry)
+        | Entry::AccentMismatch(entry) => &entry.jpreprocess,
+        _ => return None,
+    };
+    Some(phonemes.iter().map(|p| p.value.as_str()).collect())
+}
+
+impl Expectation {
+    fn matches(&self, entry: &Entry) -> bool {
+        let same_sentence = match (&self.text, &self.hash) {
+            (Some(text), _) => text == entry.original(),
+            (None, Some(hash)) => hash.eq_ignore_ascii_case(&sentence_hash(entry.original())),
+            (None, None) => false,
+        };
+        same_sentence
+            && self.kind == entry.kind()
+            && self.phonemes.as_ref().is_none_or(|phonemes| {
+                jpreprocess_phonemes(entry).is_some_and(|jp| jp == *phonemes)
+            })
+    }
+}
+
+pub struct Expectations {
+    expectations: Vec<Expectation>,
+    // Whether each expectation matched a sentence in this run.
+    used: Vec<bool>,
+}
+
+impl Expectations {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        let expectations: Vec<Expectation> =
+            serde_json::from_str(&json).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        for expectation in &expectations {
+            anyhow::ensure!(
+                expectation.text.is_some() || expectation.hash.is_some(),
+                "{}: every expectation needs a text or a hash",
+                path
+            );
+        }
+        Ok(Self {
+            used: vec![false; expectations.len()],
+            expectations,
+        })
+    }
+
+    // Wrap the entry as `expected` if an expectation covers it.
+    pub fn apply(&mut self, entry: Entry) -> Entry {
+        let Some(i) = self.expectations.iter().position(|e| e.matches(&entry)) else {
+            return entry;
+        };
+        self.used[i] = true;
+        Entry::Expected(ExpectedEntry {
+            index: entry.index(),
+            original: entry.original().to_string(),
+            note: self.expectations[i].note.clone(),
+            actual: Box::new(entry),
+        })
+    }
+
+    // Expectations that no sentence matched, i.e. differences that no longer occur.
+    pub fn stale(&self) -> Vec<Expectation> {
+        self.expectations
+            .iter()
+            .zip(&self.used)
+            .filter(|(_, used)| !**used)
+            .map(|(expectation, _)| expectation.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn light(original: &str, jp_phonemes: &[&str]) -> Entry {
+        let phonemes = jp_phonemes
+            .iter()
+            .map(|p| serde_json::json!({ "value": p, "diff": "none" }))
+            .collect::<Vec<_>>();
+        let entry = serde_json::json!({
+            "kind": "light",
+            "index": 0,
+            "original": original,
+            "openjtalk": [],
+            "jpreprocess": phonemes,
+        });
+        serde_json::from_value(entry).unwrap()
+    }
+
+    fn expectation(text: Option<&str>, hash: Option<&str>, kind: &str) -> Expectation {
+        Expectation {
+            text: text.map(str::to_string),
+            hash: hash.map(str::to_string),
+            kind: kind.to_string(),
+            phonemes: None,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn matches_by_text_or_hash() {
+        let entry = light("こんにちは", &[]);
+        let hash = sentence_hash("こんにちは").to_uppercase();
+        assert!(expectation(Some("こんにちは"), None, "light").matches(&entry));
+        assert!(expectation(None, Some(&hash), "light").matches(&entry));
+        assert!(!expectation(Some("こんばんは"), None, "light").matches(&entry));
+        assert!(!expectation(None, None, "light").matches(&entry));
+    }
+
+    #[test]
+    fn kind_and_phonemes_must_match() {
+        let entry = light("はい", &["sil", "h", "a", "i", "sil"]);
+        assert!(!expectation(Some("はい"), None, "fatal").matches(&entry));
+
+        let mut with_phonemes = expectation(Some("はい"), None, "light");
+        with_phonemes.phonemes = Some(vec![
+            "sil".into(),
+            "h".into(),
+            "a".into(),
+            "i".into(),
+            "sil".into(),
+        ]);
+        assert!(with_phonemes.matches(&entry));
+        with_phonemes.phonemes = Some(vec!["sil".into(), "h".into(), "a".into(), "sil".into()]);
+        assert!(!with_phonemes.matches(&entry));
+    }
+}
@@ -0,0 +1,98 @@
+// CI thresholds. A run that exceeds any of them exits with `FAILED_EXIT_CODE`.
+use crate::{run_diff, Entry, Results};
+use std::process::ExitCode;
+
+// 1 is what an `Err` from main exits with, so threshold failures get their own code.
+pub const FAILED_EXIT_CODE: u8 = 2;
+
+#[derive(Default)]
+pub struct Thresholds {
+    pub max_fatal: Option<usize>,
+    pub max_jp_errors: Option<usize>,
+    pub max_panics: Option<usize>,
+    // Sentences that match in the baseline run but not any more.
+    pub max_regressions: Option<usize>,
+    // Loaded while parsing the arguments, so that a bad path fails before the corpus run.
+    pub baseline: Option<Results>,
+}
+
+impl Thresholds {
+    fn is_empty(&self) -> bool {
+        self.max_fatal.is_none()
+            && self.max_jp_errors.is_none()
+            && self.max_panics.is_none()
+            && self.max_regressions.is_none()
+    }
+}
+
+pub fn parse_limit(flag: &str, value: Option<String>) -> anyhow::Result<usize> {
+    value
+        .ok_or(anyhow::anyhow!("{} requires a number", flag))?
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{}: {}", flag, e))
+}
+
+// Print one line per threshold and return the exit code for the run.
+pub fn check(thresholds: &Thresholds, results: &Results) -> ExitCode {
+    if thresholds.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+
+    // A panic on a sentence OpenJTalk also fails on is recorded as `both_error`, but still counts.
+    let panics = results
+        .files
+        .iter()
+        .flat_map(|f| &f.entries)
+        .filter(|e| match e {
+            Entry::JpPanic(_) => true,
+            Entry::BothError(e) => e
+                .jpreprocess_error
+                .as_deref()
+                .is_some_and(|message| message.contains("panicked!")),
+            _ => false,
+        })
+        .count();
+    let regressions = match (&thresholds.baseline, thresholds.max_regressions) {
+        (Some(baseline), Some(_)) => {
+            let results_diff = run_diff::diff_results(baseline, results);
+            Some(results_diff.files.iter().map(|f| f.broken.len()).sum())
+        }
+        _ => None,
+    };
+
+    let checks = [
+        (
+            "fatal mismatches",
+            Some(results.totals.fatal_mismatches),
+            thresholds.max_fatal,
+        ),
+        (
+            "jpreprocess errors",
+            Some(results.totals.jp_errors),
+            thresholds.max_jp_errors,
+        ),
+        ("jpreprocess panics", Some(panics), thresholds.max_panics),
+        ("regressions", regressions, thresholds.max_regressions),
+    ];
+
+    let mut failed = false;
+    println!();
+    for (name, value, limit) in checks {
+        let (Some(value), Some(limit)) = (value, limit) else {
+            continue;
+        };
+        if value > limit {
+            failed = true;
+            println!("\x1b[31mFAIL\x1b[0m {}: {} (max {})", name, value, limit);
+        } else {
+            println!("\x1b[32m  OK\x1b[0m {}: {} (max {})", name, value, limit);
+        }
+    }
+
+    if failed {
+        println!("\x1b[31mThresholds exceeded\x1b[0m");
+        ExitCode::from(FAILED_EXIT_CODE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
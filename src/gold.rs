@@ -0,0 +1,151 @@
+// Scoring both engines against a hand-corrected pronunciation (a gold TSV/JSONL file).
+use crate::edit::{self, EditCounts};
+use crate::mora;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineGoldScore {
+    pub phonemes: EditCounts,
+    pub moras: EditCounts,
+    pub exact: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoldScore {
+    pub reference: Vec<String>,
+    // Reference lengths the error rates are relative to.
+    pub phonemes: usize,
+    pub moras: usize,
+    pub openjtalk: EngineGoldScore,
+    pub jpreprocess: EngineGoldScore,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct EngineGoldCounts {
+    pub phoneme_errors: usize,
+    pub mora_errors: usize,
+    pub exact: usize,
+}
+
+impl EngineGoldCounts {
+    fn add_score(&mut self, score: &EngineGoldScore) {
+        self.phoneme_errors += score.phonemes.errors();
+        self.mora_errors += score.moras.errors();
+        self.exact += score.exact as usize;
+    }
+
+    fn add(&mut self, other: EngineGoldCounts) {
+        self.phoneme_errors += other.phoneme_errors;
+        self.mora_errors += other.mora_errors;
+        self.exact += other.exact;
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct GoldCounts {
+    pub sentences: usize,
+    pub phonemes: usize,
+    pub moras: usize,
+    pub openjtalk: EngineGoldCounts,
+    pub jpreprocess: EngineGoldCounts,
+}
+
+impl GoldCounts {
+    pub fn phoneme_error_rate(&self, engine: &EngineGoldCounts) -> f64 {
+        edit::error_rate(engine.phoneme_errors, self.phonemes)
+    }
+
+    pub fn mora_error_rate(&self, engine: &EngineGoldCounts) -> f64 {
+        edit::error_rate(engine.mora_errors, self.moras)
+    }
+
+    pub fn exact_match_rate(&self, engine: &EngineGoldCounts) -> f64 {
+        crate::ratio(engine.exact, self.sentences)
+    }
+
+    pub fn add_score(&mut self, score: &GoldScore) {
+        self.sentences += 1;
+        self.phonemes += score.phonemes;
+        self.moras += score.moras;
+        self.openjtalk.add_score(&score.openjtalk);
+        self.jpreprocess.add_score(&score.jpreprocess);
+    }
+
+    pub fn add(&mut self, other: GoldCounts) {
+        self.sentences += other.sentences;
+        self.phonemes += other.phonemes;
+        self.moras += other.moras;
+        self.openjtalk.add(other.openjtalk);
+        self.jpreprocess.add(other.jpreprocess);
+    }
+}
+
+// A gold pronunciation is either space-separated phonemes or katakana.
+pub fn parse_pronunciation(pronunciation: &str) -> Vec<String> {
+    if pronunciation.chars().any(|c| c.is_ascii_alphabetic()) {
+        pronunciation
+            .split_whitespace()
+            .map(str::to_string)
+            .collect()
+    } else {
+        mora::kana_to_moras(pronunciation)
+            .iter()
+            .flat_map(|m| m.split(' ').map(str::to_string))
+            .collect()
+    }
+}
+
+// Pauses and silences are left out (where to pause is not part of a pronunciation), and
+// devoiced vowels count as voiced.
+fn normalize(phonemes: &[String]) -> Vec<String> {
+    phonemes
+        .iter()
+        .filter(|p| *p != "sil" && *p != "pau")
+        .map(|p| mora::voiced(p))
+        .collect()
+}
+
+fn score_engine(
+    reference: &[String],
+    reference_moras: &[String],
+    phonemes: &[String],
+) -> EngineGoldScore {
+    let phonemes = normalize(phonemes);
+    let moras = mora::phonemes_to_moras(&phonemes);
+    EngineGoldScore {
+        phonemes: EditCounts::new(reference, &phonemes),
+        moras: EditCounts::new(reference_moras, &moras),
+        exact: phonemes == reference,
+    }
+}
+
+pub fn score(reference: &[String], ojt_phonemes: &[String], jp_phonemes: &[String]) -> GoldScore {
+    let reference = normalize(reference);
+    let reference_moras = mora::phonemes_to_moras(&reference);
+    GoldScore {
+        phonemes: reference.len(),
+        moras: reference_moras.len(),
+        openjtalk: score_engine(&reference, &reference_moras, ojt_phonemes),
+        jpreprocess: score_engine(&reference, &reference_moras, jp_phonemes),
+        reference,
+    }
+}
+
+pub fn format_gold(score: &GoldScore) -> String {
+    let format_engine = |engine: &EngineGoldScore| {
+        format!(
+            "PER {:.3}, MER {:.3}{}",
+            edit::error_rate(engine.phonemes.errors(), score.phonemes),
+            edit::error_rate(engine.moras.errors(), score.moras),
+            if engine.exact { ", exact" } else { "" }
+        )
+    };
+    format!(
+        "{} (OpenJTalk {}, JPreprocess {})",
+        score.reference.join(" "),
+        format_engine(&score.openjtalk),
+        format_engine(&score.jpreprocess)
+    )
+}
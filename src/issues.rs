@@ -0,0 +1,167 @@
+// Mismatches clustered by their diff pattern: the phonemes one engine has in place of the
+// other's, and the word they are in. One jpreprocess bug tends to show up in many sentences,
+// so the largest clusters are the issues worth fixing first.
+use crate::alignment::AlignedPhoneme;
+use crate::mora;
+use crate::njd::{NjdNode, NjdNodePair};
+use crate::DiffKind;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Example sentences kept per issue.
+const EXAMPLES: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueExample {
+    pub file: String,
+    pub index: usize,
+    pub original: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    // Space-separated phonemes, "-" if the engine has nothing in place of the other's.
+    pub openjtalk: String,
+    pub jpreprocess: String,
+    // OpenJTalk's surface string of the word the difference starts in; empty if unknown.
+    pub word: String,
+    pub diff: DiffKind,
+    // Occurrences, which can be more than one per sentence.
+    pub count: usize,
+    pub examples: Vec<IssueExample>,
+}
+
+#[derive(Default)]
+pub struct IssueClusters {
+    issues: BTreeMap<(String, String, String), Issue>,
+}
+
+// The OpenJTalk NJD node that the mora at `mora_index` belongs to.
+fn word_at(nodes: &[&NjdNode], mora_index: usize) -> Option<String> {
+    let mut start = 0;
+    for node in nodes {
+        let end = start + node.mora_size.max(0) as usize;
+        if mora_index < end {
+            return Some(node.string.clone());
+        }
+        start = end;
+    }
+    // A difference after the last mora, such as a trailing pause, is put on the last word.
+    nodes
+        .iter()
+        .rev()
+        .find(|node| node.mora_size > 0)
+        .map(|node| node.string.clone())
+}
+
+fn side(phonemes: &[&String]) -> String {
+    if phonemes.is_empty() {
+        "-".to_string()
+    } else {
+        phonemes
+            .iter()
+            .map(|p| p.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl IssueClusters {
+    // Every run of differing columns in a sentence's alignment is one occurrence.
+    pub fn add(
+        &mut self,
+        file: &str,
+        index: usize,
+        original: &str,
+        alignment: &[AlignedPhoneme],
+        njd: &[NjdNodePair],
+    ) {
+        let nodes = njd
+            .iter()
+            .filter_map(|pair| pair.openjtalk.as_ref())
+            .collect::<Vec<_>>();
+        let mut i = 0;
+        while i < alignment.len() {
+            if alignment[i].diff == DiffKind::None {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < alignment.len() && alignment[i].diff != DiffKind::None {
+                i += 1;
+            }
+            let run = &alignment[start..i];
+            let before = alignment[..start]
+                .iter()
+                .filter_map(|a| a.openjtalk.clone())
+                .collect::<Vec<_>>();
+            let word = word_at(&nodes, mora::phonemes_to_moras(&before).len()).unwrap_or_default();
+            let openjtalk = side(
+                &run.iter()
+                    .filter_map(|a| a.openjtalk.as_ref())
+                    .collect::<Vec<_>>(),
+            );
+            let jpreprocess = side(
+                &run.iter()
+                    .filter_map(|a| a.jpreprocess.as_ref())
+                    .collect::<Vec<_>>(),
+            );
+            let diff = run.iter().map(|a| a.diff).max().unwrap_or(DiffKind::None);
+
+            let issue = self
+                .issues
+                .entry((openjtalk.clone(), jpreprocess.clone(), word.clone()))
+                .or_insert_with(|| Issue {
+                    openjtalk,
+                    jpreprocess,
+                    word,
+                    diff,
+                    count: 0,
+                    examples: vec![],
+                });
+            issue.count += 1;
+            issue.diff = issue.diff.max(diff);
+            let example = IssueExample {
+                file: file.to_string(),
+                index,
+                original: original.to_string(),
+            };
+            if issue.examples.len() < EXAMPLES
+                && !issue
+                    .examples
+                    .iter()
+                    .any(|e| e.file == example.file && e.index == example.index)
+            {
+                issue.examples.push(example);
+            }
+        }
+    }
+
+    // The `limit` most frequent issues, fatal before light among equally frequent ones.
+    pub fn top(self, limit: usize) -> Vec<Issue> {
+        let mut issues = self.issues.into_values().collect::<Vec<_>>();
+        issues.sort_by(|a, b| b.count.cmp(&a.count).then(b.diff.cmp(&a.diff)));
+        issues.truncate(limit);
+        issues
+    }
+}
+
+pub fn format_issue(issue: &Issue) -> String {
+    let color = match issue.diff {
+        DiffKind::Fatal => "\x1b[31m",
+        _ => "\x1b[33m",
+    };
+    format!(
+        "{}{} → {}\x1b[0m{}",
+        color,
+        issue.openjtalk,
+        issue.jpreprocess,
+        if issue.word.is_empty() {
+            String::new()
+        } else {
+            format!(" in {}", issue.word)
+        }
+    )
+}
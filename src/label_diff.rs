@@ -0,0 +1,283 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// A run of consecutive aligned labels whose field has the same differing values in both
+// engines, e.g. an accent phrase's mora count on every label of the phrase.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelFieldDiff {
+    // The first label of the run.
+    pub openjtalk_index: usize,
+    pub jpreprocess_index: usize,
+    // Number of labels in the run, so the last one is at index + labels - 1 in both engines.
+    // 1 in results.json files written before runs were collapsed.
+    #[serde(default = "one")]
+    pub labels: usize,
+    // The phonemes of the run, space-separated.
+    #[serde(alias = "phoneme")]
+    pub phonemes: String,
+    pub field: String,
+    pub openjtalk: String,
+    pub jpreprocess: String,
+}
+
+fn one() -> usize {
+    1
+}
+
+impl LabelFieldDiff {
+    // Whether the aligned label pair extends this run.
+    fn continues_with(&self, oi: usize, ji: usize, ojt_value: &str, jp_value: &str) -> bool {
+        oi == self.openjtalk_index + self.labels
+            && ji == self.jpreprocess_index + self.labels
+            && ojt_value == self.openjtalk
+            && jp_value == self.jpreprocess
+    }
+}
+
+// Stringify a single label field the same way for both engines.
+// Undefined values are rendered as "xx", as in the full-context label format.
+trait FieldValue {
+    fn field_value(&self) -> String;
+}
+
+impl FieldValue for u8 {
+    fn field_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FieldValue for i8 {
+    fn field_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FieldValue for bool {
+    fn field_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl<T: FieldValue> FieldValue for Option<T> {
+    fn field_value(&self) -> String {
+        self.as_ref()
+            .map_or_else(|| "xx".to_string(), FieldValue::field_value)
+    }
+}
+
+macro_rules! push_section {
+    ($fields:ident, $name:literal, $section:expr, [$($field:ident),* $(,)?]) => {
+        $fields.extend([$(
+            (
+                concat!($name, ".", stringify!($field)),
+                $section.map_or_else(|| "xx".to_string(), |s| s.$field.field_value()),
+            ),
+        )*]);
+    };
+}
+
+// Flatten every section of a full-context label but the phonemes into (field name, value) pairs.
+// The phonemes are left to the phoneme diff: the quinphone context of a label next to an
+// inserted or deleted phoneme differs, even though the label itself was aligned as equal.
+fn label_fields(label: &jlabel::Label) -> Vec<(&'static str, String)> {
+    let mut fields = vec![];
+    push_section!(
+        fields,
+        "mora",
+        label.mora.as_ref(),
+        [
+            relative_accent_position,
+            position_forward,
+            position_backward
+        ]
+    );
+    push_section!(
+        fields,
+        "word_prev",
+        label.word_prev.as_ref(),
+        [pos, ctype, cform]
+    );
+    push_section!(
+        fields,
+        "word_curr",
+        label.word_curr.as_ref(),
+        [pos, ctype, cform]
+    );
+    push_section!(
+        fields,
+        "word_next",
+        label.word_next.as_ref(),
+        [pos, ctype, cform]
+    );
+    push_section!(
+        fields,
+        "accent_phrase_prev",
+        label.accent_phrase_prev.as_ref(),
+        [
+            mora_count,
+            accent_position,
+            is_interrogative,
+            is_pause_insertion
+        ]
+    );
+    push_section!(
+        fields,
+        "accent_phrase_curr",
+        label.accent_phrase_curr.as_ref(),
+        [
+            mora_count,
+            accent_position,
+            is_interrogative,
+            accent_phrase_position_forward,
+            accent_phrase_position_backward,
+            mora_position_forward,
+            mora_position_backward,
+        ]
+    );
+    push_section!(
+        fields,
+        "accent_phrase_next",
+        label.accent_phrase_next.as_ref(),
+        [
+            mora_count,
+            accent_position,
+            is_interrogative,
+            is_pause_insertion
+        ]
+    );
+    push_section!(
+        fields,
+        "breath_group_prev",
+        label.breath_group_prev.as_ref(),
+        [accent_phrase_count, mora_count]
+    );
+    push_section!(
+        fields,
+        "breath_group_curr",
+        label.breath_group_curr.as_ref(),
+        [
+            accent_phrase_count,
+            mora_count,
+            breath_group_position_forward,
+            breath_group_position_backward,
+            accent_phrase_position_forward,
+            accent_phrase_position_backward,
+            mora_position_forward,
+            mora_position_backward,
+        ]
+    );
+    push_section!(
+        fields,
+        "breath_group_next",
+        label.breath_group_next.as_ref(),
+        [accent_phrase_count, mora_count]
+    );
+    push_section!(
+        fields,
+        "utterance",
+        Some(&label.utterance),
+        [breath_group_count, accent_phrase_count, mora_count]
+    );
+    fields
+}
+
+// Compare every label field of the label pairs whose phonemes were aligned as equal, collapsing
+// repeated differences into runs. Labels on inserted/deleted/replaced phonemes are already
+// reported by the phoneme diff.
+pub fn diff_labels(
+    ojt_labels: &[jlabel::Label],
+    jp_labels: &[jlabel::Label],
+) -> Vec<LabelFieldDiff> {
+    use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+    let ojt_phonemes = ojt_labels
+        .iter()
+        .map(|l| l.phoneme.c.clone().unwrap_or_default())
+        .collect::<Vec<_>>();
+    let jp_phonemes = jp_labels
+        .iter()
+        .map(|l| l.phoneme.c.clone().unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    let mut diffs: Vec<LabelFieldDiff> = vec![];
+    // The run each field is in, as an index into `diffs`.
+    let mut open_runs: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for op in capture_diff_slices(Algorithm::Myers, &ojt_phonemes, &jp_phonemes) {
+        let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        else {
+            continue;
+        };
+        for offset in 0..len {
+            let (oi, ji) = (old_index + offset, new_index + offset);
+            let ojt_fields = label_fields(&ojt_labels[oi]);
+            let jp_fields = label_fields(&jp_labels[ji]);
+            for ((field, ojt_value), (_, jp_value)) in ojt_fields.into_iter().zip(jp_fields) {
+                if ojt_value == jp_value {
+                    continue;
+                }
+                if let Some(&run) = open_runs.get(field) {
+                    let diff = &mut diffs[run];
+                    if diff.continues_with(oi, ji, &ojt_value, &jp_value) {
+                        diff.labels += 1;
+                        diff.phonemes += " ";
+                        diff.phonemes += &ojt_phonemes[oi];
+                        continue;
+                    }
+                }
+                open_runs.insert(field, diffs.len());
+                diffs.push(LabelFieldDiff {
+                    openjtalk_index: oi,
+                    jpreprocess_index: ji,
+                    labels: 1,
+                    phonemes: ojt_phonemes[oi].clone(),
+                    field: field.to_string(),
+                    openjtalk: ojt_value,
+                    jpreprocess: jp_value,
+                });
+            }
+        }
+    }
+    diffs
+}
+
+// Number of mismatching labels per field, used for the per-file and total stats.
+pub fn count_by_field(diffs: &[LabelFieldDiff], counts: &mut BTreeMap<String, usize>) {
+    for diff in diffs {
+        *counts.entry(diff.field.clone()).or_default() += diff.labels;
+    }
+}
+
+// One line per mismatching field: how many labels disagree, with the first run.
+pub fn format_label_diffs(diffs: &[LabelFieldDiff]) -> Vec<String> {
+    let mut by_field: BTreeMap<&str, Vec<&LabelFieldDiff>> = BTreeMap::new();
+    for diff in diffs {
+        by_field.entry(&diff.field).or_default().push(diff);
+    }
+    by_field
+        .into_iter()
+        .map(|(field, diffs)| {
+            let first = diffs[0];
+            let last_index = first.openjtalk_index + first.labels - 1;
+            format!(
+                "{}: {} labels in {} runs (first at #{}{} {}: OpenJTalk {} / JPreprocess {})",
+                field,
+                diffs.iter().map(|d| d.labels).sum::<usize>(),
+                diffs.len(),
+                first.openjtalk_index,
+                if first.labels > 1 {
+                    format!("-#{}", last_index)
+                } else {
+                    String::new()
+                },
+                first.phonemes,
+                first.openjtalk,
+                first.jpreprocess
+            )
+        })
+        .collect()
+}
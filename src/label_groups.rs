@@ -0,0 +1,70 @@
+// Accent phrases, moras and words in full-context labels. Labels carry no IDs for them, so one
+// is a run of consecutive labels at the same position: an accent phrase by its breath group's
+// position in the utterance and its position in the breath group, a mora additionally by its
+// position in the accent phrase.
+use crate::mora;
+use std::ops::Range;
+
+pub fn accent_phrase_key(label: &jlabel::Label) -> Option<(u8, u8)> {
+    Some((
+        label
+            .breath_group_curr
+            .as_ref()?
+            .breath_group_position_forward,
+        label
+            .accent_phrase_curr
+            .as_ref()?
+            .accent_phrase_position_forward,
+    ))
+}
+
+pub fn mora_key(label: &jlabel::Label) -> Option<(u8, u8, u8)> {
+    let (breath_group, accent_phrase) = accent_phrase_key(label)?;
+    Some((
+        breath_group,
+        accent_phrase,
+        label.mora.as_ref()?.position_forward,
+    ))
+}
+
+// Runs of consecutive labels with the same key, as ranges of label indices. Labels without a
+// key (pauses and silences) end a run and belong to none.
+pub fn group_by<K: PartialEq>(
+    labels: &[jlabel::Label],
+    key: impl Fn(&jlabel::Label) -> Option<K>,
+) -> Vec<(K, Range<usize>)> {
+    let mut groups: Vec<(K, Range<usize>)> = vec![];
+    let mut in_run = false;
+    for (index, label) in labels.iter().enumerate() {
+        let Some(key) = key(label) else {
+            in_run = false;
+            continue;
+        };
+        match groups.last_mut() {
+            Some((last, range)) if in_run && *last == key => range.end = index + 1,
+            _ => groups.push((key, index..index + 1)),
+        }
+        in_run = true;
+    }
+    groups
+}
+
+// The phonemes of a run of labels.
+pub fn phonemes(labels: &[jlabel::Label]) -> Vec<String> {
+    labels.iter().filter_map(|l| l.phoneme.c.clone()).collect()
+}
+
+// The moras of the labels, as in `mora::phonemes_to_moras` (devoiced vowels voiced) but grouped
+// by the labels' mora positions.
+pub fn moras(labels: &[jlabel::Label]) -> Vec<String> {
+    group_by(labels, mora_key)
+        .into_iter()
+        .map(|(_, range)| {
+            phonemes(&labels[range])
+                .iter()
+                .map(|p| mora::voiced(p))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
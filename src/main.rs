@@ -1,42 +1,111 @@
+mod accent;
+mod alignment;
+mod cause;
+mod corpus;
+mod edit;
+mod expectations;
+mod gate;
+mod gold;
+mod issues;
+mod label_diff;
+mod label_groups;
+mod mora;
+mod mora_diff;
+mod njd;
+mod offenders;
+mod pauses;
+mod pitch;
+mod reading;
+mod references;
+mod ruby;
+mod run_diff;
+mod segmentation;
+mod stages;
+mod utterance;
+mod words;
+mod worker;
+
+use alignment::AlignedPhoneme;
+use cause::Cause;
+use edit::{EditDistance, EditDistanceCounts, EditStats};
+use label_diff::LabelFieldDiff;
+use mora_diff::MoraSegment;
+use njd::NjdNodePair;
 use open_jtalk::{text2mecab, JpCommon, ManagedResource, Mecab, Njd};
-use serde::Serialize;
-use std::panic;
+use pauses::PauseComparison;
+use pitch::PitchComparison;
+use references::{ReferenceCounts, ReferenceStats, References};
+use segmentation::{BoundaryCounts, Segmentation};
+use serde::{Deserialize, Serialize};
+use stages::{EngineRun, Stage, StageDivergence};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::process::ExitCode;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
 use std::time::Instant;
+use utterance::UtteranceComparison;
+use words::WordDiff;
 
 static DICT_DIR: &str = concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/data/open_jtalk_dic_utf_8-1.11"
 );
 
-static OJT_RESOURCES: std::sync::LazyLock<std::sync::Mutex<Resources>> =
-    std::sync::LazyLock::new(|| {
-        std::sync::Mutex::new(Resources {
-            mecab: ManagedResource::initialize(),
-            njd: ManagedResource::initialize(),
-            jpcommon: ManagedResource::initialize(),
-        })
-    });
+thread_local! {
+    // Each thread (each --jobs worker) has its own OpenJTalk instance, set up by `load_openjtalk`.
+    static OJT_RESOURCES: RefCell<Option<Resources>> = const { RefCell::new(None) };
+}
 
 // ---- JSON output types ----
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Results {
     generated_at: String,
     commit: String,
     totals: Stats,
     files: Vec<FileResult>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    stale_expectations: Vec<expectations::Expectation>,
+    // The most frequent light and fatal mismatch patterns across all files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    issues: Vec<issues::Issue>,
+    // The words most often pronounced differently in fatal mismatches.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    offending_words: Vec<offenders::OffendingWord>,
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
+// `default` lets `diff` read results.json files written before a stat was added.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
 struct Stats {
     total: usize,
     characters: usize,
     matches: usize,
     light_mismatches: usize,
     fatal_mismatches: usize,
+    label_mismatches: usize,
+    accent_mismatches: usize,
+    label_field_mismatches: BTreeMap<String, usize>,
+    expected: usize,
+    segmentation_mismatches: usize,
+    segmentation_precision: f64,
+    segmentation_recall: f64,
+    segmentation_f1: f64,
+    pause_mismatches: usize,
+    utterance_mismatches: usize,
+    interrogative_mismatches: usize,
+    // Sentences with a word tagged differently, and the words by the two parts of speech.
+    word_mismatches: usize,
+    pos_confusions: BTreeMap<String, usize>,
+    // Light and fatal mismatches by their likely cause.
+    mismatch_causes: BTreeMap<Cause, usize>,
+    #[serde(flatten)]
+    edits: EditStats,
+    #[serde(flatten)]
+    references: ReferenceStats,
     jp_errors: usize,
     ojt_errors: usize,
     openjtalk_extraction_duration_ms: f64,
@@ -45,7 +114,7 @@ struct Stats {
     jpreprocess_throughput_chars_per_second: f64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct FileResult {
     file: String,
@@ -53,7 +122,7 @@ struct FileResult {
     entries: Vec<Entry>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "camelCase")]
 enum Entry {
     #[serde(rename = "match")]
@@ -62,6 +131,10 @@ enum Entry {
     Light(MismatchEntry),
     #[serde(rename = "fatal")]
     Fatal(MismatchEntry),
+    #[serde(rename = "label_mismatch")]
+    LabelMismatch(MismatchEntry),
+    #[serde(rename = "accent_mismatch")]
+    AccentMismatch(MismatchEntry),
     #[serde(rename = "jp_error")]
     JpError(ErrorEntry),
     #[serde(rename = "ojt_error")]
@@ -70,29 +143,122 @@ enum Entry {
     BothError(ErrorEntry),
     #[serde(rename = "jp_panic")]
     JpPanic(ErrorEntry),
+    #[serde(rename = "expected")]
+    Expected(ExpectedEntry),
+}
+
+impl Entry {
+    // The serialized `kind` tag.
+    fn kind(&self) -> &'static str {
+        match self {
+            Entry::Match(_) => "match",
+            Entry::Light(_) => "light",
+            Entry::Fatal(_) => "fatal",
+            Entry::LabelMismatch(_) => "label_mismatch",
+            Entry::AccentMismatch(_) => "accent_mismatch",
+            Entry::JpError(_) => "jp_error",
+            Entry::OjtError(_) => "ojt_error",
+            Entry::BothError(_) => "both_error",
+            Entry::JpPanic(_) => "jp_panic",
+            Entry::Expected(_) => "expected",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Entry::Match(entry) => entry.index,
+            Entry::Light(entry)
+            | Entry::Fatal(entry)
+            | Entry::LabelMismatch(entry)
+            | Entry::AccentMismatch(entry) => entry.index,
+            Entry::JpError(entry)
+            | Entry::OjtError(entry)
+            | Entry::BothError(entry)
+            | Entry::JpPanic(entry) => entry.index,
+            Entry::Expected(entry) => entry.index,
+        }
+    }
+
+    fn original(&self) -> &str {
+        match self {
+            Entry::Match(entry) => &entry.original,
+            Entry::Light(entry)
+            | Entry::Fatal(entry)
+            | Entry::LabelMismatch(entry)
+            | Entry::AccentMismatch(entry) => &entry.original,
+            Entry::JpError(entry)
+            | Entry::OjtError(entry)
+            | Entry::BothError(entry)
+            | Entry::JpPanic(entry) => &entry.original,
+            Entry::Expected(entry) => &entry.original,
+        }
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct MatchEntry {
     index: usize,
     original: String,
     openjtalk: Vec<Phoneme>,
     jpreprocess: Vec<Phoneme>,
+    // jpreprocess against OpenJTalk. Absent from results.json files written before it was added.
+    #[serde(default)]
+    edits: EditDistance,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    segmentation: Option<Segmentation>,
+    #[serde(flatten)]
+    references: References,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct MismatchEntry {
     index: usize,
     original: String,
     openjtalk: Vec<Phoneme>,
     jpreprocess: Vec<Phoneme>,
+    // Both engines' phonemes side by side. Only for light and fatal mismatches; the phonemes
+    // of a label mismatch are the same.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alignment: Vec<AlignedPhoneme>,
+    // The same at the mora level, also only for light and fatal mismatches.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    moras: Vec<MoraSegment>,
+    // jpreprocess against OpenJTalk. Absent from results.json files written before it was added.
+    #[serde(default)]
+    edits: EditDistance,
     #[serde(skip_serializing_if = "Option::is_none")]
     length_mismatch: Option<bool>,
+    // The likely cause of a light or fatal mismatch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cause: Option<Cause>,
+    // Both engines' accent phrases and pitch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pitch: Option<PitchComparison>,
+    // Only if the engines pause in different places.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pauses: Option<PauseComparison>,
+    // Only if the engines' utterance-level features differ.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    utterance: Option<UtteranceComparison>,
+    // Words both engines pronounce the same but tag with a different part of speech or
+    // conjugation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    words: Vec<WordDiff>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    label_diffs: Vec<LabelFieldDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    divergence: Option<StageDivergence>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    njd: Vec<NjdNodePair>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    segmentation: Option<Segmentation>,
+    #[serde(flatten)]
+    references: References,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ErrorEntry {
     index: usize,
@@ -103,13 +269,29 @@ struct ErrorEntry {
     jpreprocess_error: Option<String>,
 }
 
-#[derive(Serialize)]
+// A mismatch or error listed in the --expectations file.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExpectedEntry {
+    index: usize,
+    original: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    // The entry the sentence would have had without the expectation.
+    actual: Box<Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
 struct Phoneme {
     value: String,
     diff: DiffKind,
+    // The equivalence rule that made a difference light (or fatal) rather than a substitution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rule: Option<String>,
 }
 
-#[derive(Serialize)]
+// In increasing order of severity.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "camelCase")]
 enum DiffKind {
     None,
@@ -117,97 +299,14 @@ enum DiffKind {
     Fatal,
 }
 
-// Compute per-phoneme diffs between two phoneme sequences using LCS-based diffing.
-// Equal phonemes get DiffKind::None, case-insensitive matches get DiffKind::Light,
-// and all other differences (including insertions/deletions) get DiffKind::Fatal.
-fn phonemes_with_diff(primary: &[String], other: &[String]) -> Vec<Phoneme> {
-    use similar::{capture_diff_slices, Algorithm, DiffOp};
-
-    let ops = capture_diff_slices(Algorithm::Myers, primary, other);
-    let mut result = Vec::new();
-
-    for op in ops {
-        match op {
-            DiffOp::Equal { old_index, len, .. } => {
-                for p in &primary[old_index..old_index + len] {
-                    result.push(Phoneme {
-                        value: p.clone(),
-                        diff: DiffKind::None,
-                    });
-                }
-            }
-            DiffOp::Delete {
-                old_index, old_len, ..
-            } => {
-                for p in &primary[old_index..old_index + old_len] {
-                    result.push(Phoneme {
-                        value: p.clone(),
-                        diff: DiffKind::Fatal,
-                    });
-                }
-            }
-            DiffOp::Insert { .. } => {
-                // Other has extra phonemes not in primary; nothing to annotate here.
-            }
-            DiffOp::Replace {
-                old_index,
-                old_len,
-                new_index,
-                new_len,
-            } => {
-                let old_slice = &primary[old_index..old_index + old_len];
-                let new_slice = &other[new_index..new_index + new_len];
-                // Run a nested LCS on lowercased phonemes to identify case-insensitive
-                // (Light) matches within the replaced block.
-                let old_lower: Vec<String> = old_slice.iter().map(|s| s.to_lowercase()).collect();
-                let new_lower: Vec<String> = new_slice.iter().map(|s| s.to_lowercase()).collect();
-                for inner_op in capture_diff_slices(Algorithm::Myers, &old_lower, &new_lower) {
-                    match inner_op {
-                        DiffOp::Equal {
-                            old_index: oi, len, ..
-                        } => {
-                            // Case-insensitive match within the replaced block.
-                            for p in &old_slice[oi..oi + len] {
-                                result.push(Phoneme {
-                                    value: p.clone(),
-                                    diff: DiffKind::Light,
-                                });
-                            }
-                        }
-                        DiffOp::Delete {
-                            old_index: oi,
-                            old_len: ol,
-                            ..
-                        } => {
-                            for p in &old_slice[oi..oi + ol] {
-                                result.push(Phoneme {
-                                    value: p.clone(),
-                                    diff: DiffKind::Fatal,
-                                });
-                            }
-                        }
-                        DiffOp::Insert { .. } => {
-                            // Extra phonemes in other not in primary; skip.
-                        }
-                        DiffOp::Replace {
-                            old_index: oi,
-                            old_len: ol,
-                            ..
-                        } => {
-                            for p in &old_slice[oi..oi + ol] {
-                                result.push(Phoneme {
-                                    value: p.clone(),
-                                    diff: DiffKind::Fatal,
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-        }
+// Accuracies and boundary precision/recall; 0 when there is nothing to get right, so read them
+// together with their counts.
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
     }
-
-    result
 }
 
 fn throughput_chars_per_second(characters: usize, extraction_duration_ms: f64) -> f64 {
@@ -218,30 +317,639 @@ fn throughput_chars_per_second(characters: usize, extraction_duration_ms: f64) -
     }
 }
 
-fn main() -> anyhow::Result<()> {
+// What one sentence contributes to the results. Produced on a --jobs thread and
+// consumed on the main thread in sentence order.
+struct SentenceOutcome {
+    entry: Entry,
+    // Terminal output, printed once every earlier sentence has been printed.
+    report: Vec<String>,
+    segmentation_counts: BoundaryCounts,
+    openjtalk_extraction_duration_ms: f64,
+    jpreprocess_extraction_duration_ms: f64,
+}
+
+// Entry for a sentence at least one engine failed on.
+fn error_entry(
+    prefix: &str,
+    sentence_i: usize,
+    sentence: &str,
+    r1: anyhow::Result<EngineRun>,
+    r2: anyhow::Result<EngineRun>,
+    report: &mut Vec<String>,
+) -> Entry {
+    let ojt_err = r1.as_ref().err().map(|e| e.to_string());
+    let jp_err = r2.as_ref().err().map(|e| e.to_string());
+    let kind = if r1.is_err() && r2.is_err() {
+        "Both"
+    } else if r1.is_err() {
+        "OpenJTalk"
+    } else if r2
+        .as_ref()
+        .is_err_and(|e| e.to_string().contains("panicked!"))
     {
-        let mut resources = OJT_RESOURCES.lock().unwrap();
-        resources.mecab.load(DICT_DIR)?;
+        "JPreprocess (panicked)"
+    } else {
+        "JPreprocess"
+    };
+    report.push(format!("{} \x1b[35m{} Error:\x1b[0m", prefix, kind));
+    report.push(expectations::format_original(sentence));
+    report.push(format!("    OpenJTalk: {:?}", r1.map(|_| ())));
+    report.push(format!("  JPreprocess: {:?}", r2.map(|_| ())));
+
+    let error_entry = ErrorEntry {
+        index: sentence_i,
+        original: sentence.to_string(),
+        openjtalk_error: ojt_err,
+        jpreprocess_error: jp_err,
+    };
+    match kind {
+        "Both" => Entry::BothError(error_entry),
+        "OpenJTalk" => Entry::OjtError(error_entry),
+        "JPreprocess (panicked)" => Entry::JpPanic(error_entry),
+        _ => Entry::JpError(error_entry),
     }
+}
 
-    // Parse --json <path> from args
+// Compare the labels of a sentence both engines got through, and score them against the
+// sentence's references, if it has any.
+// The NJD snapshots come from the same engine runs as the labels.
+#[allow(clippy::too_many_arguments)]
+fn compare_sentence(
+    prefix: &str,
+    sentence_i: usize,
+    corpus_sentence: &corpus::Sentence,
+    ojt_run: EngineRun,
+    jp_run: EngineRun,
+    rules: &alignment::Rules,
+    report: &mut Vec<String>,
+    segmentation_counts: &mut BoundaryCounts,
+) -> Entry {
+    let sentence = corpus_sentence.text.as_str();
+    let (ojt_labels, jp_labels) = (&ojt_run.labels[..], &jp_run.labels[..]);
+    let ojt_phonemes = ojt_labels
+        .iter()
+        .filter_map(|l| l.phoneme.c.clone())
+        .collect::<Vec<_>>();
+    let jp_phonemes = jp_labels
+        .iter()
+        .filter_map(|l| l.phoneme.c.clone())
+        .collect::<Vec<_>>();
+    let label_diffs = label_diff::diff_labels(ojt_labels, jp_labels);
+    let alignment = rules.align(&ojt_phonemes, &jp_phonemes);
+    // Not necessarily the same phonemes: with --ignore-pauses, pauses may differ.
+    let phonemes_match = alignment
+        .iter()
+        .all(|aligned| aligned.diff == DiffKind::None);
+    // Segmentation, ruby and the stage divergence are all read from the snapshots.
+    let snapshots = ojt_run
+        .snapshots
+        .and_then(|ojt_snapshots| Ok((ojt_snapshots, jp_run.snapshots?)))
+        .inspect_err(|e| {
+            eprintln!("{}failed to take NJD snapshots: {}", prefix, e);
+        })
+        .ok();
+    let segmentation = snapshots.as_ref().and_then(|(ojt, jp)| {
+        let (counts, segmentation) =
+            segmentation::compare(segmentation::tokens(ojt), segmentation::tokens(jp));
+        segmentation_counts.add(counts);
+        segmentation
+    });
+    let references = References::score(
+        corpus_sentence,
+        references::EngineOutput {
+            ojt_labels,
+            jp_labels,
+            ojt_phonemes: &ojt_phonemes,
+            jp_phonemes: &jp_phonemes,
+            final_nodes: snapshots
+                .as_ref()
+                .map(|(ojt, jp)| (stages::final_nodes(ojt), stages::final_nodes(jp))),
+        },
+    );
+    let divergence = snapshots
+        .as_ref()
+        .map(|(ojt, jp)| stages::first_divergence(ojt, jp));
+    let njd_nodes = snapshots
+        .as_ref()
+        .map(|(ojt, jp)| njd::align_nodes(stages::final_nodes(ojt), stages::final_nodes(jp)))
+        .unwrap_or_default();
+    if phonemes_match && label_diffs.is_empty() {
+        let (phonemes, phonemes_jp) = alignment::phonemes_with_diff(&alignment);
+        let edits = alignment::alignment_edits(&alignment);
+        Entry::Match(MatchEntry {
+            index: sentence_i,
+            original: sentence.to_string(),
+            openjtalk: phonemes,
+            jpreprocess: phonemes_jp,
+            edits: EditDistance::new(ojt_labels, jp_labels, edits),
+            segmentation,
+            references,
+        })
+    } else if phonemes_match {
+        // The same phonemes with a different accent matter more than other label differences.
+        let pitch = pitch::compare(ojt_labels, jp_labels);
+        let accent_mismatch = pitch.differs();
+        if accent_mismatch {
+            report.push(format!("{}\x1b[34mAccent mismatch:\x1b[0m", prefix));
+        } else {
+            report.push(format!("{}\x1b[36mLabel mismatch:\x1b[0m", prefix));
+        }
+        report.push(expectations::format_original(sentence));
+        report.push(format!("     Phonemes: {}", ojt_phonemes.join(" ")));
+        let pauses = pauses::compare(ojt_labels, jp_labels);
+        if pauses.differs() {
+            report.push(format!("       Pauses: {}", pauses::format_pauses(&pauses)));
+        }
+        let utterance = utterance::compare(ojt_labels, jp_labels);
+        if utterance.differs() {
+            report.push(format!(
+                "    Utterance: {}",
+                utterance::format_utterance(&utterance)
+            ));
+        }
+        let word_diffs = words::diff_words(ojt_labels, jp_labels);
+        for diff in &word_diffs {
+            report.push(format!("         Word: {}", words::format_word_diff(diff)));
+        }
+        if accent_mismatch {
+            report.push(format!(
+                "    OpenJTalk: {}",
+                pitch::format_pitch(&pitch.openjtalk)
+            ));
+            report.push(format!(
+                "  JPreprocess: {}",
+                pitch::format_pitch(&pitch.jpreprocess)
+            ));
+        }
+        if let Some(segmentation) = &segmentation {
+            report.push(format!(
+                " Segmentation: {}",
+                segmentation::format_segmentation(segmentation)
+            ));
+        }
+        references.report(report);
+        if let Some(divergence) = &divergence {
+            report.push(format!(
+                "  Diverges at: {}",
+                stages::format_divergence(divergence)
+            ));
+        }
+        for pair in njd_nodes.iter().filter(|p| !p.equal) {
+            report.push(format!("          NJD: {}", njd::format_node_pair(pair)));
+        }
+        for line in label_diff::format_label_diffs(&label_diffs) {
+            report.push(format!("        Label: {}", line));
+        }
+
+        let (phonemes, phonemes_jp) = alignment::phonemes_with_diff(&alignment);
+        let edits = alignment::alignment_edits(&alignment);
+        let entry = MismatchEntry {
+            index: sentence_i,
+            original: sentence.to_string(),
+            openjtalk: phonemes,
+            jpreprocess: phonemes_jp,
+            alignment: if ojt_phonemes == jp_phonemes {
+                vec![]
+            } else {
+                alignment
+            },
+            moras: vec![],
+            edits: EditDistance::new(ojt_labels, jp_labels, edits),
+            length_mismatch: None,
+            cause: None,
+            pitch: Some(pitch),
+            pauses: pauses.differs().then_some(pauses),
+            utterance: utterance.differs().then_some(utterance),
+            words: word_diffs,
+            label_diffs,
+            divergence,
+            njd: njd_nodes,
+            segmentation,
+            references,
+        };
+        if accent_mismatch {
+            Entry::AccentMismatch(entry)
+        } else {
+            Entry::LabelMismatch(entry)
+        }
+    } else {
+        let (phonemes_ojt, phonemes_jp) = alignment::phonemes_with_diff(&alignment);
+        let edits = EditDistance::new(
+            ojt_labels,
+            jp_labels,
+            alignment::alignment_edits(&alignment),
+        );
+
+        let is_fatal = alignment
+            .iter()
+            .any(|aligned| matches!(aligned.diff, DiffKind::Fatal));
+        let length_mismatch = ojt_phonemes.len() != jp_phonemes.len();
+
+        if is_fatal {
+            if length_mismatch {
+                report.push(format!(
+                    "{}\x1b[31mFatal mismatch: (length mismatch: OpenJTalk: {}, JPreprocess: {})\x1b[0m",
+                    prefix,
+                    ojt_phonemes.len(),
+                    jp_phonemes.len()
+                ));
+            } else {
+                report.push(format!("{}\x1b[31mFatal mismatch:\x1b[0m", prefix));
+            }
+        } else {
+            report.push(format!("{}\x1b[33mLight mismatch:\x1b[0m", prefix));
+        }
+
+        let (ojt_line, jp_line) = alignment::format_alignment(&alignment);
+        report.push(expectations::format_original(sentence));
+        report.push(format!("    OpenJTalk: {}", ojt_line));
+        report.push(format!("  JPreprocess: {}", jp_line));
+        let moras = mora_diff::diff_moras(ojt_labels, jp_labels, &phonemes_ojt, &phonemes_jp);
+        report.push(format!(
+            "        Moras: {}",
+            mora_diff::format_mora_diff(&moras)
+        ));
+        let pauses = pauses::compare(ojt_labels, jp_labels);
+        if pauses.differs() {
+            report.push(format!("       Pauses: {}", pauses::format_pauses(&pauses)));
+        }
+        let utterance = utterance::compare(ojt_labels, jp_labels);
+        if utterance.differs() {
+            report.push(format!(
+                "    Utterance: {}",
+                utterance::format_utterance(&utterance)
+            ));
+        }
+        let word_diffs = words::diff_words(ojt_labels, jp_labels);
+        for diff in &word_diffs {
+            report.push(format!("         Word: {}", words::format_word_diff(diff)));
+        }
+        let pitch = pitch::compare(ojt_labels, jp_labels);
+        if pitch.differs() {
+            report.push(format!(
+                "        Pitch: OpenJTalk {}, JPreprocess {}",
+                pitch::format_pitch(&pitch.openjtalk),
+                pitch::format_pitch(&pitch.jpreprocess)
+            ));
+        }
+        let cause = cause::classify(divergence.as_ref(), segmentation.as_ref());
+        report.push(format!("        Cause: {}", cause.name()));
+        let rule_names = alignment::format_rules(&alignment);
+        if !rule_names.is_empty() {
+            report.push(format!("        Rules: {}", rule_names));
+        }
+        report.push(format!(
+            "        Edits: PER {:.3} ({}), MER {:.3} ({})",
+            edits.phoneme_error_rate,
+            edit::format_edits(&edits.phoneme_edits),
+            edits.mora_error_rate,
+            edit::format_edits(&edits.mora_edits)
+        ));
+        if let Some(segmentation) = &segmentation {
+            report.push(format!(
+                " Segmentation: {}",
+                segmentation::format_segmentation(segmentation)
+            ));
+        }
+        references.report(report);
+        if let Some(divergence) = &divergence {
+            report.push(format!(
+                "  Diverges at: {}",
+                stages::format_divergence(divergence)
+            ));
+        }
+        for pair in njd_nodes.iter().filter(|p| !p.equal) {
+            report.push(format!("          NJD: {}", njd::format_node_pair(pair)));
+        }
+
+        let entry = MismatchEntry {
+            index: sentence_i,
+            original: sentence.to_string(),
+            openjtalk: phonemes_ojt,
+            jpreprocess: phonemes_jp,
+            alignment,
+            moras,
+            edits,
+            length_mismatch: if length_mismatch { Some(true) } else { None },
+            cause: Some(cause),
+            pitch: Some(pitch),
+            pauses: pauses.differs().then_some(pauses),
+            utterance: utterance.differs().then_some(utterance),
+            words: word_diffs,
+            label_diffs,
+            divergence,
+            njd: njd_nodes,
+            segmentation,
+            references,
+        };
+        if is_fatal {
+            Entry::Fatal(entry)
+        } else {
+            Entry::Light(entry)
+        }
+    }
+}
+
+// One --jobs thread: take the next unclaimed sentence until there are none left, with this
+// thread's own OpenJTalk and jpreprocess instances (and worker processes, if any).
+fn process_sentences(
+    files: &[(String, Vec<corpus::Sentence>)],
+    sentence_refs: &[(usize, usize)],
+    next_sentence: &AtomicUsize,
+    isolate_openjtalk: bool,
+    rules: &alignment::Rules,
+    sender: mpsc::Sender<(usize, SentenceOutcome)>,
+) -> anyhow::Result<()> {
+    if !isolate_openjtalk {
+        load_openjtalk()?;
+    }
+    #[cfg(not(feature = "panic_safe"))]
+    let mut jp = jpreprocess::JPreprocess::with_dictionaries(
+        jpreprocess::SystemDictionaryConfig::Bundled(
+            jpreprocess::kind::JPreprocessDictionaryKind::NaistJdic,
+        )
+        .load()?,
+        None,
+    );
+    #[cfg(feature = "panic_safe")]
+    let mut jp_worker = worker::Worker::spawn(worker::Engine::Jpreprocess)?;
+    // With --isolate-openjtalk, OpenJTalk runs only in a supervised worker process, which
+    // sends the NJD snapshots along with the labels.
+    let mut ojt_worker = if isolate_openjtalk {
+        Some(worker::Worker::spawn(worker::Engine::OpenJtalk)?)
+    } else {
+        None
+    };
+
+    loop {
+        let position = next_sentence.fetch_add(1, Ordering::Relaxed);
+        let Some(&(file_i, sentence_i)) = sentence_refs.get(position) else {
+            return Ok(());
+        };
+        let (file_name, sentences) = &files[file_i];
+        let sentence = &sentences[sentence_i].text;
+        let prefix = format!(
+            "[{} : {} / {}]: ",
+            file_name,
+            sentence_i + 1,
+            sentences.len()
+        );
+        let mut openjtalk_extraction_duration_ms = 0.0f64;
+        let mut jpreprocess_extraction_duration_ms = 0.0f64;
+        let ojt_run = match &mut ojt_worker {
+            Some(ojt_worker) => {
+                let extraction = ojt_worker.run(sentence)?;
+                openjtalk_extraction_duration_ms += extraction.duration_ms;
+                extraction.run
+            }
+            None => {
+                let openjtalk_extraction_started = Instant::now();
+                let ojt_run = stages::run_openjtalk(sentence);
+                openjtalk_extraction_duration_ms +=
+                    openjtalk_extraction_started.elapsed().as_secs_f64() * 1000.0;
+                ojt_run
+            }
+        };
+
+        // With panic_safe, jpreprocess only runs in a worker process that survives panics,
+        // aborts and stack overflows, NJD snapshots included, so this thread has no instance
+        // of its own. Without it, panics propagate with their backtrace.
+        #[cfg(feature = "panic_safe")]
+        let jp_run = {
+            let extraction = jp_worker.run(sentence)?;
+            jpreprocess_extraction_duration_ms += extraction.duration_ms;
+            extraction.run
+        };
+        #[cfg(not(feature = "panic_safe"))]
+        let jp_run = {
+            let jpreprocess_extraction_started = Instant::now();
+            let jp_run = stages::run_jpreprocess(sentence, |sentence| {
+                jp.text_to_njd(sentence).map_err(anyhow::Error::from)
+            });
+            jpreprocess_extraction_duration_ms +=
+                jpreprocess_extraction_started.elapsed().as_secs_f64() * 1000.0;
+            match jp_run {
+                Err(e) if e.is::<stages::StagesOutdated>() => return Err(e),
+                jp_run => jp_run,
+            }
+        };
+
+        let mut report = vec![];
+        let mut segmentation_counts = BoundaryCounts::default();
+        let entry = match (ojt_run, jp_run) {
+            (Ok(ojt_run), Ok(jp_run)) => compare_sentence(
+                &prefix,
+                sentence_i,
+                &sentences[sentence_i],
+                ojt_run,
+                jp_run,
+                rules,
+                &mut report,
+                &mut segmentation_counts,
+            ),
+            (r1, r2) => {
+                // 念のためリセット (with panic_safe, the worker restarts itself after a crash)
+                #[cfg(not(feature = "panic_safe"))]
+                if r2.is_err() {
+                    jp = jpreprocess::JPreprocess::with_dictionaries(
+                        jpreprocess::SystemDictionaryConfig::Bundled(
+                            jpreprocess::kind::JPreprocessDictionaryKind::NaistJdic,
+                        )
+                        .load()?,
+                        None,
+                    );
+                }
+                error_entry(&prefix, sentence_i, sentence, r1, r2, &mut report)
+            }
+        };
+        let outcome = SentenceOutcome {
+            entry,
+            report,
+            segmentation_counts,
+            openjtalk_extraction_duration_ms,
+            jpreprocess_extraction_duration_ms,
+        };
+        if sender.send((position, outcome)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let subcommand = match std::env::args().nth(1).as_deref() {
+        Some(worker::OPENJTALK_WORKER_ARG) => worker::run_openjtalk_worker(),
+        #[cfg(feature = "panic_safe")]
+        Some(worker::JPREPROCESS_WORKER_ARG) => worker::run_jpreprocess_worker(),
+        Some("diff") => run_diff::run(std::env::args().skip(2)),
+        _ => return compare_corpus(),
+    };
+    subcommand.map(|()| ExitCode::SUCCESS)
+}
+
+fn compare_corpus() -> anyhow::Result<ExitCode> {
+    // Parse --json <path>, --jobs <n>, --isolate-openjtalk, --expectations <path>,
+    // --equivalences <path>, --ignore-pauses, --keep-sentence-end and the CI thresholds from args
     let raw_args: Vec<String> = std::env::args().skip(1).collect();
     let mut json_path: Option<String> = None;
+    let mut jobs = 1usize;
+    let mut isolate_openjtalk = false;
+    let mut rules = alignment::Rules::default();
+    let mut ignore_pauses = false;
+    let mut keep_sentence_end = false;
+    let mut thresholds = gate::Thresholds::default();
+    let mut expectations: Option<expectations::Expectations> = None;
     let mut file_paths: Vec<std::path::PathBuf> = vec![];
     {
         let mut iter = raw_args.into_iter();
         while let Some(arg) = iter.next() {
             if arg == "--json" {
                 json_path = iter.next();
+            } else if arg == "--jobs" {
+                jobs = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--jobs requires a number"))?
+                    .parse()?;
+                anyhow::ensure!(jobs > 0, "--jobs must be at least 1");
+            } else if arg == "--isolate-openjtalk" {
+                isolate_openjtalk = true;
+            } else if arg == "--max-fatal" {
+                thresholds.max_fatal = Some(gate::parse_limit(&arg, iter.next())?);
+            } else if arg == "--max-jp-errors" {
+                thresholds.max_jp_errors = Some(gate::parse_limit(&arg, iter.next())?);
+            } else if arg == "--max-panics" {
+                thresholds.max_panics = Some(gate::parse_limit(&arg, iter.next())?);
+            } else if arg == "--max-regressions" {
+                thresholds.max_regressions = Some(gate::parse_limit(&arg, iter.next())?);
+            } else if arg == "--baseline" {
+                let path = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--baseline requires a path"))?;
+                thresholds.baseline = Some(run_diff::load_results(&path)?);
+            } else if arg == "--expectations" {
+                let path = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--expectations requires a path"))?;
+                expectations = Some(expectations::Expectations::load(&path)?);
+            } else if arg == "--ignore-pauses" {
+                ignore_pauses = true;
+            } else if arg == "--keep-sentence-end" {
+                keep_sentence_end = true;
+            } else if arg == "--equivalences" {
+                let path = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--equivalences requires a path"))?;
+                rules = alignment::Rules::load(&path)?;
             } else {
                 file_paths.push(std::path::PathBuf::from(arg));
             }
         }
     }
+    if ignore_pauses {
+        rules.ignore_pauses();
+    }
+    anyhow::ensure!(
+        thresholds.max_regressions.is_none() || thresholds.baseline.is_some(),
+        "--max-regressions requires --baseline <path>"
+    );
+    anyhow::ensure!(
+        thresholds.baseline.is_none() || thresholds.max_regressions.is_some(),
+        "--baseline requires --max-regressions <n>"
+    );
+
+    // Fail early if the dictionary is missing, rather than on every sentence of every thread.
+    // With --isolate-openjtalk, only the workers load it.
+    if !isolate_openjtalk {
+        load_openjtalk()?;
+    }
+
+    let files = file_paths
+        .iter()
+        .map(|file| -> anyhow::Result<(String, Vec<corpus::Sentence>)> {
+            Ok((
+                file.file_name().unwrap().to_string_lossy().to_string(),
+                corpus::load(file, keep_sentence_end)?,
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // Every sentence of every file, in output order. Threads take the next unclaimed one,
+    // and their outcomes are put back into this order before anything is printed or counted.
+    let sentence_refs = files
+        .iter()
+        .enumerate()
+        .flat_map(|(file_i, (_, sentences))| {
+            (0..sentences.len()).map(move |sentence_i| (file_i, sentence_i))
+        })
+        .collect::<Vec<_>>();
+    let mut outcomes: Vec<Option<SentenceOutcome>> = sentence_refs.iter().map(|_| None).collect();
+    let next_sentence = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| -> anyhow::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        let handles = (0..jobs)
+            .map(|_| {
+                let sender = sender.clone();
+                let (files, sentence_refs, next_sentence, rules) =
+                    (&files, &sentence_refs, &next_sentence, &rules);
+                scope.spawn(move || {
+                    process_sentences(
+                        files,
+                        sentence_refs,
+                        next_sentence,
+                        isolate_openjtalk,
+                        rules,
+                        sender,
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(sender);
+
+        // Print each sentence's report as soon as all the sentences before it are in.
+        let mut printed = 0;
+        for (position, mut outcome) in receiver {
+            if let Some(expectations) = &mut expectations {
+                outcome.entry = expectations.apply(outcome.entry);
+                if let Entry::Expected(entry) = &outcome.entry {
+                    outcome.report.push(format!(
+                        "     Expected: {}",
+                        entry.note.as_deref().unwrap_or(entry.actual.kind())
+                    ));
+                }
+            }
+            outcomes[position] = Some(outcome);
+            while let Some(Some(outcome)) = outcomes.get(printed) {
+                for line in &outcome.report {
+                    println!("{}", line);
+                }
+                printed += 1;
+            }
+        }
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        Ok(())
+    })?;
 
     let mut total_matches = 0usize;
     let mut total_light_mismatches = 0usize;
     let mut total_fatal_mismatches = 0usize;
+    let mut total_label_mismatches = 0usize;
+    let mut total_accent_mismatches = 0usize;
+    let mut total_expected = 0usize;
+    let mut total_label_field_mismatches: BTreeMap<String, usize> = BTreeMap::new();
+    let mut total_segmentation_mismatches = 0usize;
+    let mut total_pause_mismatches = 0usize;
+    let mut total_utterance_mismatches = 0usize;
+    let mut total_interrogative_mismatches = 0usize;
+    let mut total_word_mismatches = 0usize;
+    let mut total_pos_confusions: BTreeMap<String, usize> = BTreeMap::new();
+    let mut total_mismatch_causes: BTreeMap<Cause, usize> = BTreeMap::new();
+    let mut issue_clusters = issues::IssueClusters::default();
+    let mut offending_words = offenders::OffendingWords::default();
+    let mut total_segmentation_counts = BoundaryCounts::default();
+    let mut total_edit_counts = EditDistanceCounts::default();
+    let mut total_reference_counts = ReferenceCounts::default();
     let mut total_jp_errors = 0usize;
     let mut total_ojt_errors = 0usize;
     let mut total_characters = 0usize;
@@ -251,177 +959,121 @@ fn main() -> anyhow::Result<()> {
     let mut file_stats_display = vec![];
     let mut all_file_results: Vec<FileResult> = vec![];
 
-    let mut jp = jpreprocess::JPreprocess::with_dictionaries(
-        jpreprocess::SystemDictionaryConfig::Bundled(
-            jpreprocess::kind::JPreprocessDictionaryKind::NaistJdic,
-        )
-        .load()?,
-        None,
-    );
-
-    for file in &file_paths {
-        let text = std::fs::read_to_string(file)?;
-        let sentences = lazy_regex::regex!("[。「」]")
-            .split(&text)
-            .map(|s| lazy_regex::regex_replace_all!(r"\s+", s, ""))
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<_>>();
-
+    let mut outcomes = outcomes
+        .into_iter()
+        .map(|outcome| outcome.expect("every sentence has been processed"));
+    for (file_name, sentences) in &files {
         let sentences_size = sentences.len();
-        let characters = sentences.iter().map(|s| s.chars().count()).sum::<usize>();
+        let characters = sentences
+            .iter()
+            .map(|s| s.text.chars().count())
+            .sum::<usize>();
+        // Extraction times are summed over sentences, so with --jobs they are engine time,
+        // not wall-clock time.
         let mut openjtalk_extraction_duration_ms = 0.0f64;
         let mut jpreprocess_extraction_duration_ms = 0.0f64;
         let mut matches = 0usize;
         let mut light_mismatches = 0usize;
         let mut fatal_mismatches = 0usize;
+        let mut label_mismatches = 0usize;
+        let mut accent_mismatches = 0usize;
+        let mut expected = 0usize;
+        let mut label_field_mismatches: BTreeMap<String, usize> = BTreeMap::new();
+        let mut segmentation_mismatches = 0usize;
+        let mut pause_mismatches = 0usize;
+        let mut utterance_mismatches = 0usize;
+        let mut interrogative_mismatches = 0usize;
+        let mut word_mismatches = 0usize;
+        let mut pos_confusions: BTreeMap<String, usize> = BTreeMap::new();
+        let mut mismatch_causes: BTreeMap<Cause, usize> = BTreeMap::new();
+        let mut segmentation_counts = BoundaryCounts::default();
+        let mut edit_counts = EditDistanceCounts::default();
+        let mut reference_counts = ReferenceCounts::default();
         let mut jp_errors = 0usize;
         let mut ojt_errors = 0usize;
         let mut entries: Vec<Entry> = vec![];
 
-        for (sentence_i, sentence) in sentences.iter().enumerate() {
-            let prefix = format!(
-                "[{} : {} / {}]: ",
-                file.file_name().unwrap().to_string_lossy(),
-                sentence_i + 1,
-                sentences_size
-            );
-            let openjtalk_extraction_started = Instant::now();
-            let ojt_labels = extract_fullcontext(sentence);
-            openjtalk_extraction_duration_ms +=
-                openjtalk_extraction_started.elapsed().as_secs_f64() * 1000.0;
-
-            let jpreprocess_extraction_started = Instant::now();
-            let jp_labels = panic::catch_unwind(panic::AssertUnwindSafe(|| {
-                jp.extract_fullcontext(sentence)
-                    .map_err(anyhow::Error::from)
-            }))
-            .map_err(|e| anyhow::anyhow!("panicked! {:?}", e.downcast_ref::<String>()))
-            .and_then(|r| r);
-            jpreprocess_extraction_duration_ms +=
-                jpreprocess_extraction_started.elapsed().as_secs_f64() * 1000.0;
-
-            let (ojt_labels, jp_labels) = match (ojt_labels, jp_labels) {
-                (Ok(ojt_labels), Ok(jp_labels)) => (ojt_labels, jp_labels),
-                (r1, r2) => {
-                    let ojt_err = r1.as_ref().err().map(|e| e.to_string());
-                    let jp_err = r2.as_ref().err().map(|e| e.to_string());
-                    if r1.is_err() {
-                        ojt_errors += 1;
+        for outcome in outcomes.by_ref().take(sentences_size) {
+            openjtalk_extraction_duration_ms += outcome.openjtalk_extraction_duration_ms;
+            jpreprocess_extraction_duration_ms += outcome.jpreprocess_extraction_duration_ms;
+            segmentation_counts.add(outcome.segmentation_counts);
+            match &outcome.entry {
+                Entry::Match(_) => matches += 1,
+                Entry::Light(_) => light_mismatches += 1,
+                Entry::Fatal(_) => fatal_mismatches += 1,
+                Entry::LabelMismatch(_) => label_mismatches += 1,
+                Entry::AccentMismatch(_) => accent_mismatches += 1,
+                Entry::JpError(_) | Entry::JpPanic(_) => jp_errors += 1,
+                Entry::OjtError(_) => ojt_errors += 1,
+                Entry::BothError(_) => {
+                    jp_errors += 1;
+                    ojt_errors += 1;
+                }
+                Entry::Expected(_) => expected += 1,
+            }
+            // Expected mismatches are known issues, so they are left out of the clusters.
+            if let Entry::Light(entry) | Entry::Fatal(entry) = &outcome.entry {
+                issue_clusters.add(
+                    file_name,
+                    entry.index,
+                    &entry.original,
+                    &entry.alignment,
+                    &entry.njd,
+                );
+            }
+            if let Entry::Fatal(entry) = &outcome.entry {
+                offending_words.add(file_name, &entry.njd);
+            }
+            let actual = match &outcome.entry {
+                Entry::Expected(entry) => &entry.actual,
+                entry => entry,
+            };
+            let segmentation = match actual {
+                Entry::Match(entry) => {
+                    edit_counts.add_distance(&entry.edits);
+                    reference_counts.add_references(&entry.references);
+                    entry.segmentation.as_ref()
+                }
+                Entry::Light(entry)
+                | Entry::Fatal(entry)
+                | Entry::LabelMismatch(entry)
+                | Entry::AccentMismatch(entry) => {
+                    label_diff::count_by_field(&entry.label_diffs, &mut label_field_mismatches);
+                    if entry.pauses.is_some() {
+                        pause_mismatches += 1;
+                    }
+                    if let Some(cause) = entry.cause {
+                        *mismatch_causes.entry(cause).or_default() += 1;
                     }
-                    if r2.is_err() {
-                        jp_errors += 1;
-                        // 念のためリセット
-                        jp = jpreprocess::JPreprocess::with_dictionaries(
-                            jpreprocess::SystemDictionaryConfig::Bundled(
-                                jpreprocess::kind::JPreprocessDictionaryKind::NaistJdic,
-                            )
-                            .load()?,
-                            None,
-                        );
+                    if !entry.words.is_empty() {
+                        word_mismatches += 1;
+                        words::count_pos_confusions(&entry.words, &mut pos_confusions);
                     }
-                    let kind = if r1.is_err() && r2.is_err() {
-                        "Both"
-                    } else if r1.is_err() {
-                        "OpenJTalk"
-                    } else if r2.as_ref().unwrap_err().to_string().contains("panicked!") {
-                        "JPreprocess (panicked)"
-                    } else {
-                        "JPreprocess"
-                    };
-                    println!("{} \x1b[35m{} Error:\x1b[0m", prefix, kind);
-                    println!("     Original: {}", sentence);
-                    println!("    OpenJTalk: {:?}", r1.map(|_| ()));
-                    println!("  JPreprocess: {:?}", r2.map(|_| ()));
-
-                    let error_entry = ErrorEntry {
-                        index: sentence_i,
-                        original: sentence.to_string(),
-                        openjtalk_error: ojt_err,
-                        jpreprocess_error: jp_err,
-                    };
-                    entries.push(match kind {
-                        "Both" => Entry::BothError(error_entry),
-                        "OpenJTalk" => Entry::OjtError(error_entry),
-                        "JPreprocess (panicked)" => Entry::JpPanic(error_entry),
-                        _ => Entry::JpError(error_entry),
-                    });
-                    continue;
+                    if let Some(utterance) = &entry.utterance {
+                        utterance_mismatches += 1;
+                        if utterance.interrogative_differs() {
+                            interrogative_mismatches += 1;
+                        }
+                    }
+                    edit_counts.add_distance(&entry.edits);
+                    reference_counts.add_references(&entry.references);
+                    entry.segmentation.as_ref()
                 }
-            };
-            let ojt_phonemes = ojt_labels
-                .iter()
-                .filter_map(|l| l.phoneme.c.clone())
-                .collect::<Vec<_>>();
-            let jp_phonemes = jp_labels
-                .iter()
-                .filter_map(|l| l.phoneme.c.clone())
-                .collect::<Vec<_>>();
-            if ojt_phonemes == jp_phonemes {
-                matches += 1;
-                let phonemes = phonemes_with_diff(&ojt_phonemes, &jp_phonemes);
-                let phonemes_jp = phonemes_with_diff(&jp_phonemes, &ojt_phonemes);
-                entries.push(Entry::Match(MatchEntry {
-                    index: sentence_i,
-                    original: sentence.to_string(),
-                    openjtalk: phonemes,
-                    jpreprocess: phonemes_jp,
-                }));
-            } else {
-                let phonemes_ojt = phonemes_with_diff(&ojt_phonemes, &jp_phonemes);
-                let phonemes_jp = phonemes_with_diff(&jp_phonemes, &ojt_phonemes);
-
-                let is_fatal = phonemes_ojt
-                    .iter()
-                    .chain(phonemes_jp.iter())
-                    .any(|p| matches!(p.diff, DiffKind::Fatal));
-                let length_mismatch = ojt_phonemes.len() != jp_phonemes.len();
-
-                if is_fatal {
-                    if length_mismatch {
-                        println!(
-                            "{}\x1b[31mFatal mismatch: (length mismatch: OpenJTalk: {}, JPreprocess: {})\x1b[0m",
-                            prefix,
-                            ojt_phonemes.len(),
-                            jp_phonemes.len()
-                        );
-                    } else {
-                        println!("{}\x1b[31mFatal mismatch:\x1b[0m", prefix);
+                Entry::JpError(entry)
+                | Entry::OjtError(entry)
+                | Entry::BothError(entry)
+                | Entry::JpPanic(entry) => {
+                    if sentences[entry.index].has_reference() {
+                        reference_counts.unscored_sentences += 1;
                     }
-                    fatal_mismatches += 1;
-                } else {
-                    println!("{}\x1b[33mLight mismatch:\x1b[0m", prefix);
-                    light_mismatches += 1;
+                    None
                 }
-
-                let format_phonemes = |phonemes: &[Phoneme]| -> String {
-                    phonemes
-                        .iter()
-                        .map(|p| match p.diff {
-                            DiffKind::None => p.value.clone(),
-                            DiffKind::Light => format!("\x1b[33m{}\x1b[0m", p.value),
-                            DiffKind::Fatal => format!("\x1b[31m{}\x1b[0m", p.value),
-                        })
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                };
-
-                println!("     Original: {}", sentence);
-                println!("    OpenJTalk: {}", format_phonemes(&phonemes_ojt));
-                println!("  JPreprocess: {}", format_phonemes(&phonemes_jp));
-
-                let entry = MismatchEntry {
-                    index: sentence_i,
-                    original: sentence.to_string(),
-                    openjtalk: phonemes_ojt,
-                    jpreprocess: phonemes_jp,
-                    length_mismatch: if length_mismatch { Some(true) } else { None },
-                };
-                entries.push(if is_fatal {
-                    Entry::Fatal(entry)
-                } else {
-                    Entry::Light(entry)
-                });
+                Entry::Expected(_) => None,
+            };
+            if segmentation.is_some() {
+                segmentation_mismatches += 1;
             }
+            entries.push(outcome.entry);
         }
 
         let openjtalk_throughput_chars_per_second =
@@ -429,28 +1081,57 @@ fn main() -> anyhow::Result<()> {
         let jpreprocess_throughput_chars_per_second =
             throughput_chars_per_second(characters, jpreprocess_extraction_duration_ms);
         file_stats_display.push(format!(
-            "{}: \x1b[32m{} matches\x1b[0m, \x1b[33m{} light mismatches\x1b[0m, \x1b[31m{} fatal mismatches\x1b[0m, \x1b[35m{} jpreprocess errors\x1b[0m, \x1b[35m{} open_jtalk errors\x1b[0m, OpenJTalk: {:.0} chars/s ({:.2} ms), JPreprocess: {:.0} chars/s ({:.2} ms), {} chars",
-            file.file_name().unwrap().to_string_lossy(),
+            "{}: \x1b[32m{} matches\x1b[0m, \x1b[33m{} light mismatches\x1b[0m, \x1b[31m{} fatal mismatches\x1b[0m, \x1b[36m{} label mismatches\x1b[0m, \x1b[34m{} accent mismatches\x1b[0m, \x1b[90m{} expected\x1b[0m, {} segmentation mismatches (F1 {:.3}), {} pause mismatches, {} utterance mismatches ({} interrogative), {} word tag mismatches, PER {:.4}, MER {:.4}, \x1b[35m{} jpreprocess errors\x1b[0m, \x1b[35m{} open_jtalk errors\x1b[0m, OpenJTalk: {:.0} chars/s ({:.2} ms), JPreprocess: {:.0} chars/s ({:.2} ms), {} chars{}{}",
+            file_name,
             matches,
             light_mismatches,
             fatal_mismatches,
+            label_mismatches,
+            accent_mismatches,
+            expected,
+            segmentation_mismatches,
+            segmentation_counts.f1(),
+            pause_mismatches,
+            utterance_mismatches,
+            interrogative_mismatches,
+            word_mismatches,
+            edit_counts.phoneme_error_rate(),
+            edit_counts.mora_error_rate(),
             jp_errors,
             ojt_errors,
             openjtalk_throughput_chars_per_second,
             openjtalk_extraction_duration_ms,
             jpreprocess_throughput_chars_per_second,
             jpreprocess_extraction_duration_ms,
-            characters
+            characters,
+            cause::format_counts(&mismatch_causes),
+            reference_counts.format()
         ));
 
         all_file_results.push(FileResult {
-            file: file.file_name().unwrap().to_string_lossy().to_string(),
+            file: file_name.clone(),
             stats: Stats {
                 total: sentences_size,
                 characters,
                 matches,
                 light_mismatches,
                 fatal_mismatches,
+                label_mismatches,
+                accent_mismatches,
+                label_field_mismatches: label_field_mismatches.clone(),
+                expected,
+                segmentation_mismatches,
+                segmentation_precision: segmentation_counts.precision(),
+                segmentation_recall: segmentation_counts.recall(),
+                segmentation_f1: segmentation_counts.f1(),
+                pause_mismatches,
+                utterance_mismatches,
+                interrogative_mismatches,
+                word_mismatches,
+                pos_confusions: pos_confusions.clone(),
+                mismatch_causes: mismatch_causes.clone(),
+                edits: edit_counts.stats(),
+                references: reference_counts.stats(),
                 jp_errors,
                 ojt_errors,
                 openjtalk_extraction_duration_ms,
@@ -464,6 +1145,26 @@ fn main() -> anyhow::Result<()> {
         total_matches += matches;
         total_light_mismatches += light_mismatches;
         total_fatal_mismatches += fatal_mismatches;
+        total_label_mismatches += label_mismatches;
+        total_accent_mismatches += accent_mismatches;
+        total_expected += expected;
+        for (field, count) in label_field_mismatches {
+            *total_label_field_mismatches.entry(field).or_default() += count;
+        }
+        total_segmentation_mismatches += segmentation_mismatches;
+        total_pause_mismatches += pause_mismatches;
+        total_utterance_mismatches += utterance_mismatches;
+        total_interrogative_mismatches += interrogative_mismatches;
+        total_word_mismatches += word_mismatches;
+        for (pair, count) in pos_confusions {
+            *total_pos_confusions.entry(pair).or_default() += count;
+        }
+        for (cause, count) in mismatch_causes {
+            *total_mismatch_causes.entry(cause).or_default() += count;
+        }
+        total_segmentation_counts.add(segmentation_counts);
+        total_edit_counts.add(edit_counts);
+        total_reference_counts.add(reference_counts);
         total_jp_errors += jp_errors;
         total_ojt_errors += ojt_errors;
         total_characters += characters;
@@ -481,83 +1182,191 @@ fn main() -> anyhow::Result<()> {
     let total_jpreprocess_throughput_chars_per_second =
         throughput_chars_per_second(total_characters, total_jpreprocess_extraction_duration_ms);
     println!(
-        "Total: \x1b[32m{} matches\x1b[0m, \x1b[33m{} light mismatches\x1b[0m, \x1b[31m{} fatal mismatches\x1b[0m, \x1b[35m{} jpreprocess errors\x1b[0m, \x1b[35m{} open_jtalk errors\x1b[0m, OpenJTalk: {:.0} chars/s ({:.2} ms), JPreprocess: {:.0} chars/s ({:.2} ms), {} chars",
+        "Total: \x1b[32m{} matches\x1b[0m, \x1b[33m{} light mismatches\x1b[0m, \x1b[31m{} fatal mismatches\x1b[0m, \x1b[36m{} label mismatches\x1b[0m, \x1b[34m{} accent mismatches\x1b[0m, \x1b[90m{} expected\x1b[0m, {} segmentation mismatches (F1 {:.3}), {} pause mismatches, {} utterance mismatches ({} interrogative), {} word tag mismatches, PER {:.4}, MER {:.4}, \x1b[35m{} jpreprocess errors\x1b[0m, \x1b[35m{} open_jtalk errors\x1b[0m, OpenJTalk: {:.0} chars/s ({:.2} ms), JPreprocess: {:.0} chars/s ({:.2} ms), {} chars{}{}",
         total_matches,
         total_light_mismatches,
         total_fatal_mismatches,
+        total_label_mismatches,
+        total_accent_mismatches,
+        total_expected,
+        total_segmentation_mismatches,
+        total_segmentation_counts.f1(),
+        total_pause_mismatches,
+        total_utterance_mismatches,
+        total_interrogative_mismatches,
+        total_word_mismatches,
+        total_edit_counts.phoneme_error_rate(),
+        total_edit_counts.mora_error_rate(),
         total_jp_errors,
         total_ojt_errors,
         total_openjtalk_throughput_chars_per_second,
         total_openjtalk_extraction_duration_ms,
         total_jpreprocess_throughput_chars_per_second,
         total_jpreprocess_extraction_duration_ms,
-        total_characters
+        total_characters,
+        cause::format_counts(&total_mismatch_causes),
+        total_reference_counts.format()
     );
 
+    let total_sentences: usize = all_file_results.iter().map(|f| f.stats.total).sum();
+    let results = Results {
+        generated_at: chrono::Local::now().to_rfc3339(),
+        commit: std::env::var("GITHUB_SHA").unwrap_or_else(|_| "local".to_string()),
+        totals: Stats {
+            total: total_sentences,
+            characters: total_characters,
+            matches: total_matches,
+            light_mismatches: total_light_mismatches,
+            fatal_mismatches: total_fatal_mismatches,
+            label_mismatches: total_label_mismatches,
+            accent_mismatches: total_accent_mismatches,
+            label_field_mismatches: total_label_field_mismatches,
+            expected: total_expected,
+            segmentation_mismatches: total_segmentation_mismatches,
+            segmentation_precision: total_segmentation_counts.precision(),
+            segmentation_recall: total_segmentation_counts.recall(),
+            segmentation_f1: total_segmentation_counts.f1(),
+            pause_mismatches: total_pause_mismatches,
+            utterance_mismatches: total_utterance_mismatches,
+            interrogative_mismatches: total_interrogative_mismatches,
+            word_mismatches: total_word_mismatches,
+            pos_confusions: total_pos_confusions,
+            mismatch_causes: total_mismatch_causes,
+            edits: total_edit_counts.stats(),
+            references: total_reference_counts.stats(),
+            jp_errors: total_jp_errors,
+            ojt_errors: total_ojt_errors,
+            openjtalk_extraction_duration_ms: total_openjtalk_extraction_duration_ms,
+            openjtalk_throughput_chars_per_second: total_openjtalk_throughput_chars_per_second,
+            jpreprocess_extraction_duration_ms: total_jpreprocess_extraction_duration_ms,
+            jpreprocess_throughput_chars_per_second: total_jpreprocess_throughput_chars_per_second,
+        },
+        files: all_file_results,
+        stale_expectations: expectations
+            .as_ref()
+            .map(expectations::Expectations::stale)
+            .unwrap_or_default(),
+        issues: issue_clusters.top(100),
+        offending_words: offending_words.top(200),
+    };
+    let pos_confusions = words::top_pos_confusions(&results.totals.pos_confusions, 10);
+    if !pos_confusions.is_empty() {
+        println!("Part-of-speech disagreements (OpenJTalk / JPreprocess):");
+        for (pair, count) in pos_confusions {
+            println!("{:>7} {}", count, pair);
+        }
+    }
+    if !results.issues.is_empty() {
+        println!("Top issues (OpenJTalk → JPreprocess):");
+        for issue in results.issues.iter().take(20) {
+            println!("{:>7} {}", issue.count, issues::format_issue(issue));
+            for example in &issue.examples {
+                println!(
+                    "          {} #{}: {}",
+                    example.file,
+                    example.index + 1,
+                    example.original
+                );
+            }
+        }
+    }
+    if !results.offending_words.is_empty() {
+        println!("Top offending words in fatal mismatches (OpenJTalk / JPreprocess):");
+        for word in results.offending_words.iter().take(30) {
+            println!(
+                "{:>7} {}",
+                word.count,
+                offenders::format_offending_word(word)
+            );
+        }
+    }
+    for expectation in &results.stale_expectations {
+        println!(
+            "\x1b[90mStale expectation:\x1b[0m {} ({}){}",
+            expectation
+                .text
+                .as_deref()
+                .or(expectation.hash.as_deref())
+                .unwrap_or_default(),
+            expectation.kind,
+            expectation
+                .note
+                .as_ref()
+                .map(|note| format!(": {}", note))
+                .unwrap_or_default()
+        );
+    }
+
     if let Some(path) = json_path {
-        let total_sentences: usize = all_file_results.iter().map(|f| f.stats.total).sum();
-        let results = Results {
-            generated_at: chrono::Local::now().to_rfc3339(),
-            commit: std::env::var("GITHUB_SHA").unwrap_or_else(|_| "local".to_string()),
-            totals: Stats {
-                total: total_sentences,
-                characters: total_characters,
-                matches: total_matches,
-                light_mismatches: total_light_mismatches,
-                fatal_mismatches: total_fatal_mismatches,
-                jp_errors: total_jp_errors,
-                ojt_errors: total_ojt_errors,
-                openjtalk_extraction_duration_ms: total_openjtalk_extraction_duration_ms,
-                openjtalk_throughput_chars_per_second: total_openjtalk_throughput_chars_per_second,
-                jpreprocess_extraction_duration_ms: total_jpreprocess_extraction_duration_ms,
-                jpreprocess_throughput_chars_per_second:
-                    total_jpreprocess_throughput_chars_per_second,
-            },
-            files: all_file_results,
-        };
         let json = serde_json::to_string(&results)?;
         std::fs::write(&path, json)?;
         eprintln!("JSON written to {}", path);
     }
 
-    Ok(())
+    Ok(gate::check(&thresholds, &results))
 }
 
-fn extract_fullcontext(text: &str) -> anyhow::Result<Vec<jlabel::Label>> {
-    let Resources {
-        mecab,
-        njd,
-        jpcommon,
-    } = &mut *OJT_RESOURCES.lock().unwrap();
-
-    jpcommon.refresh();
-    njd.refresh();
-    mecab.refresh();
-
-    let mecab_text = text2mecab(text)?;
-    if mecab.analysis(mecab_text) {
-        njd.mecab2njd(
-            mecab
-                .get_feature()
-                .ok_or(anyhow::anyhow!("mecab.get_feature()"))?,
-            mecab.get_size(),
-        );
-        njd.set_pronunciation();
-        njd.set_digit();
-        njd.set_accent_phrase();
-        njd.set_accent_type();
-        njd.set_unvoiced_vowel();
-        njd.set_long_vowel();
-        jpcommon.njd2jpcommon(njd);
-        jpcommon.make_label();
-        jpcommon
-            .get_label_feature_to_iter()
-            .ok_or(anyhow::anyhow!("jpcommon.get_label_feature_to_iter()"))
-            .map(|iter| iter.map(|s| jlabel::Label::from_str(s).unwrap()).collect())
-            .map_err(Into::into)
-    } else {
-        anyhow::bail!("mecab.analysis() failed")
-    }
+// Run the OpenJTalk frontend, handing the NJD to `on_stage` after each NJD stage.
+fn extract_fullcontext_with(
+    text: &str,
+    mut on_stage: impl FnMut(Stage, &Njd),
+) -> anyhow::Result<Vec<jlabel::Label>> {
+    OJT_RESOURCES.with_borrow_mut(|resources| {
+        let Resources {
+            mecab,
+            njd,
+            jpcommon,
+        } = resources
+            .as_mut()
+            .ok_or(anyhow::anyhow!("OpenJTalk is not loaded on this thread"))?;
+
+        jpcommon.refresh();
+        njd.refresh();
+        mecab.refresh();
+
+        let mecab_text = text2mecab(text)?;
+        if mecab.analysis(mecab_text) {
+            njd.mecab2njd(
+                mecab
+                    .get_feature()
+                    .ok_or(anyhow::anyhow!("mecab.get_feature()"))?,
+                mecab.get_size(),
+            );
+            on_stage(Stage::Mecab2njd, njd);
+            njd.set_pronunciation();
+            on_stage(Stage::SetPronunciation, njd);
+            njd.set_digit();
+            on_stage(Stage::SetDigit, njd);
+            njd.set_accent_phrase();
+            on_stage(Stage::SetAccentPhrase, njd);
+            njd.set_accent_type();
+            on_stage(Stage::SetAccentType, njd);
+            njd.set_unvoiced_vowel();
+            on_stage(Stage::SetUnvoicedVowel, njd);
+            njd.set_long_vowel();
+            on_stage(Stage::SetLongVowel, njd);
+            jpcommon.njd2jpcommon(njd);
+            jpcommon.make_label();
+            jpcommon
+                .get_label_feature_to_iter()
+                .ok_or(anyhow::anyhow!("jpcommon.get_label_feature_to_iter()"))
+                .map(|iter| iter.map(|s| jlabel::Label::from_str(s).unwrap()).collect())
+        } else {
+            anyhow::bail!("mecab.analysis() failed")
+        }
+    })
+}
+
+// Load the dictionary into this thread's OpenJTalk instance.
+fn load_openjtalk() -> anyhow::Result<()> {
+    let mut resources = Resources {
+        mecab: ManagedResource::initialize(),
+        njd: ManagedResource::initialize(),
+        jpcommon: ManagedResource::initialize(),
+    };
+    resources.mecab.load(DICT_DIR)?;
+    OJT_RESOURCES.set(Some(resources));
+    Ok(())
 }
 
 struct Resources {
@@ -565,6 +1374,3 @@ struct Resources {
     njd: ManagedResource<Njd>,
     jpcommon: ManagedResource<JpCommon>,
 }
-
-unsafe impl Send for Resources {}
-unsafe impl Sync for Resources {}
@@ -0,0 +1,156 @@
+// Conversion between katakana readings and phonemes, one mora at a time.
+// A mora is represented by its phonemes joined with spaces ("ky o"), which is what both
+// engines' labels give us and what a reading can be normalized to.
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+// Katakana → phonemes, as in OpenJTalk's jpcommon mora list. Where two spellings share
+// phonemes (ジ/ヂ, ズ/ヅ), the usual one comes first so that it wins when converting back.
+#[rustfmt::skip]
+const MORAS: &[(&str, &str)] = &[
+    ("ア", "a"), ("イ", "i"), ("ウ", "u"), ("エ", "e"), ("オ", "o"),
+    ("カ", "k a"), ("キ", "k i"), ("ク", "k u"), ("ケ", "k e"), ("コ", "k o"),
+    ("キャ", "ky a"), ("キュ", "ky u"), ("キェ", "ky e"), ("キョ", "ky o"),
+    ("ガ", "g a"), ("ギ", "g i"), ("グ", "g u"), ("ゲ", "g e"), ("ゴ", "g o"),
+    ("ギャ", "gy a"), ("ギュ", "gy u"), ("ギェ", "gy e"), ("ギョ", "gy o"),
+    ("サ", "s a"), ("シ", "sh i"), ("ス", "s u"), ("セ", "s e"), ("ソ", "s o"),
+    ("シャ", "sh a"), ("シュ", "sh u"), ("シェ", "sh e"), ("ショ", "sh o"), ("スィ", "s i"),
+    ("ザ", "z a"), ("ジ", "j i"), ("ズ", "z u"), ("ゼ", "z e"), ("ゾ", "z o"),
+    ("ジャ", "j a"), ("ジュ", "j u"), ("ジェ", "j e"), ("ジョ", "j o"), ("ズィ", "z i"),
+    ("タ", "t a"), ("チ", "ch i"), ("ツ", "ts u"), ("テ", "t e"), ("ト", "t o"),
+    ("チャ", "ch a"), ("チュ", "ch u"), ("チェ", "ch e"), ("チョ", "ch o"),
+    ("ティ", "t i"), ("トゥ", "t u"), ("テュ", "ty u"),
+    ("ツァ", "ts a"), ("ツィ", "ts i"), ("ツェ", "ts e"), ("ツォ", "ts o"),
+    ("ダ", "d a"), ("ヂ", "j i"), ("ヅ", "z u"), ("デ", "d e"), ("ド", "d o"),
+    ("ディ", "d i"), ("ドゥ", "d u"), ("デュ", "dy u"),
+    ("ヂャ", "j a"), ("ヂュ", "j u"), ("ヂェ", "j e"), ("ヂョ", "j o"),
+    ("ナ", "n a"), ("ニ", "n i"), ("ヌ", "n u"), ("ネ", "n e"), ("ノ", "n o"),
+    ("ニャ", "ny a"), ("ニュ", "ny u"), ("ニェ", "ny e"), ("ニョ", "ny o"),
+    ("ハ", "h a"), ("ヒ", "h i"), ("フ", "f u"), ("ヘ", "h e"), ("ホ", "h o"),
+    ("ヒャ", "hy a"), ("ヒュ", "hy u"), ("ヒェ", "hy e"), ("ヒョ", "hy o"),
+    ("ファ", "f a"), ("フィ", "f i"), ("フェ", "f e"), ("フォ", "f o"), ("フュ", "fy u"),
+    ("バ", "b a"), ("ビ", "b i"), ("ブ", "b u"), ("ベ", "b e"), ("ボ", "b o"),
+    ("ビャ", "by a"), ("ビュ", "by u"), ("ビェ", "by e"), ("ビョ", "by o"),
+    ("パ", "p a"), ("ピ", "p i"), ("プ", "p u"), ("ペ", "p e"), ("ポ", "p o"),
+    ("ピャ", "py a"), ("ピュ", "py u"), ("ピェ", "py e"), ("ピョ", "py o"),
+    ("マ", "m a"), ("ミ", "m i"), ("ム", "m u"), ("メ", "m e"), ("モ", "m o"),
+    ("ミャ", "my a"), ("ミュ", "my u"), ("ミェ", "my e"), ("ミョ", "my o"),
+    ("ヤ", "y a"), ("ユ", "y u"), ("イェ", "y e"), ("ヨ", "y o"),
+    ("ラ", "r a"), ("リ", "r i"), ("ル", "r u"), ("レ", "r e"), ("ロ", "r o"),
+    ("リャ", "ry a"), ("リュ", "ry u"), ("リェ", "ry e"), ("リョ", "ry o"),
+    ("ワ", "w a"), ("ウィ", "w i"), ("ウェ", "w e"), ("ウォ", "w o"),
+    ("ヰ", "i"), ("ヱ", "e"), ("ヲ", "o"),
+    ("ヴァ", "v a"), ("ヴィ", "v i"), ("ヴ", "v u"), ("ヴェ", "v e"), ("ヴォ", "v o"),
+    ("ン", "N"), ("ッ", "cl"),
+    ("ァ", "a"), ("ィ", "i"), ("ゥ", "u"), ("ェ", "e"), ("ォ", "o"),
+    ("ャ", "y a"), ("ュ", "y u"), ("ョ", "y o"), ("ヮ", "w a"), ("ヵ", "k a"), ("ヶ", "k e"),
+];
+
+static KANA_TO_MORA: LazyLock<HashMap<&str, &str>> =
+    LazyLock::new(|| MORAS.iter().copied().collect());
+
+static MORA_TO_KANA: LazyLock<HashMap<&str, &str>> = LazyLock::new(|| {
+    let mut map = HashMap::new();
+    for (kana, mora) in MORAS {
+        map.entry(*mora).or_insert(*kana);
+    }
+    map
+});
+
+fn to_katakana(c: char) -> char {
+    match c {
+        'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+// Split a kana reading into moras. The long vowel mark repeats the previous vowel, and
+// anything that is not kana (punctuation, accent marks, separators) is dropped.
+pub fn kana_to_moras(kana: &str) -> Vec<String> {
+    let chars = kana.chars().map(to_katakana).collect::<Vec<_>>();
+    let mut moras: Vec<String> = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == 'ー' {
+            if let Some(vowel) = moras.last().and_then(|m| m.split(' ').next_back()) {
+                if matches!(vowel, "a" | "i" | "u" | "e" | "o") {
+                    moras.push(vowel.to_string());
+                }
+            }
+            i += 1;
+            continue;
+        }
+        let two = chars.get(i..i + 2).map(|c| c.iter().collect::<String>());
+        if let Some(mora) = two.as_deref().and_then(|two| KANA_TO_MORA.get(two)) {
+            moras.push(mora.to_string());
+            i += 2;
+        } else if let Some(mora) = KANA_TO_MORA.get(chars[i].to_string().as_str()) {
+            moras.push(mora.to_string());
+            i += 1;
+        } else {
+            i += 1;
+        }
+    }
+    moras
+}
+
+// A devoiced vowel (upper case in the labels) as its voiced counterpart.
+pub fn voiced(phoneme: &str) -> String {
+    match phoneme {
+        "A" | "I" | "U" | "E" | "O" => phoneme.to_lowercase(),
+        _ => phoneme.to_string(),
+    }
+}
+
+// Group label phonemes into moras. Pauses and silences are dropped, and devoiced vowels
+// (upper case in the labels) count as their voiced counterparts.
+pub fn phonemes_to_moras(phonemes: &[String]) -> Vec<String> {
+    let mut moras = vec![];
+    let mut consonant: Option<String> = None;
+    for phoneme in phonemes {
+        let phoneme = voiced(phoneme);
+        match phoneme.as_str() {
+            "sil" | "pau" => consonant = None,
+            "a" | "i" | "u" | "e" | "o" => moras.push(match consonant.take() {
+                Some(consonant) => format!("{} {}", consonant, phoneme),
+                None => phoneme,
+            }),
+            "N" | "cl" => {
+                consonant = None;
+                moras.push(phoneme);
+            }
+            _ => consonant = Some(phoneme),
+        }
+    }
+    moras
+}
+
+pub fn moras_to_kana(moras: &[String]) -> String {
+    moras
+        .iter()
+        .map(|mora| MORA_TO_KANA.get(mora.as_str()).copied().unwrap_or("?"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kana_to_moras_splits_digraphs_and_long_vowels() {
+        assert_eq!(
+            kana_to_moras("キョーハ、ガッコー"),
+            ["ky o", "o", "h a", "g a", "cl", "k o", "o"]
+        );
+    }
+
+    #[test]
+    fn kana_to_moras_reads_hiragana_and_drops_other_characters() {
+        assert_eq!(
+            kana_to_moras("しんぶん'/ヴァ"),
+            ["sh i", "N", "b u", "N", "v a"]
+        );
+        // A long vowel mark after "ン" has no vowel to repeat.
+        assert_eq!(kana_to_moras("ンー"), ["N"]);
+    }
+}
@@ -0,0 +1,108 @@
+// Mora-level differences: a wrong mora is one difference rather than two or three scattered
+// phoneme differences. Moras are taken from the labels, not guessed from the phonemes.
+use crate::label_groups;
+use crate::mora;
+use crate::{DiffKind, Phoneme};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+// A mora and the range of its phonemes in the sentence's phonemes (one per label).
+struct LabelMora {
+    phonemes: String,
+    range: Range<usize>,
+}
+
+// A run of moras: equal ones, or the moras one engine has in place of the other's. The text
+// is katakana, and empty on the side that has nothing in place of the other's moras.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoraSegment {
+    pub openjtalk: String,
+    pub jpreprocess: String,
+    pub diff: DiffKind,
+}
+
+// Pauses and silences belong to no mora.
+fn label_moras(labels: &[jlabel::Label]) -> Vec<LabelMora> {
+    label_groups::group_by(labels, label_groups::mora_key)
+        .into_iter()
+        .map(|(_, range)| LabelMora {
+            phonemes: label_groups::phonemes(&labels[range.clone()]).join(" "),
+            range,
+        })
+        .collect()
+}
+
+fn kana(moras: &[LabelMora]) -> String {
+    let voiced = moras
+        .iter()
+        .map(|m| {
+            m.phonemes
+                .split(' ')
+                .map(mora::voiced)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>();
+    mora::moras_to_kana(&voiced)
+}
+
+// Align the two engines' moras. A differing run is as severe as the worst phoneme difference
+// in it, and at least light: moras can also differ only in how phonemes are grouped.
+pub fn diff_moras(
+    ojt_labels: &[jlabel::Label],
+    jp_labels: &[jlabel::Label],
+    ojt_phonemes: &[Phoneme],
+    jp_phonemes: &[Phoneme],
+) -> Vec<MoraSegment> {
+    use similar::{capture_diff_slices, Algorithm};
+
+    let ojt_moras = label_moras(ojt_labels);
+    let jp_moras = label_moras(jp_labels);
+    let ojt_keys = ojt_moras.iter().map(|m| &m.phonemes).collect::<Vec<_>>();
+    let jp_keys = jp_moras.iter().map(|m| &m.phonemes).collect::<Vec<_>>();
+    let worst_diff = |moras: &[LabelMora], phonemes: &[Phoneme]| {
+        moras
+            .iter()
+            .flat_map(|m| phonemes.get(m.range.clone()).unwrap_or_default())
+            .map(|p| p.diff)
+            .max()
+            .unwrap_or(DiffKind::None)
+    };
+
+    let mut segments = vec![];
+    for op in capture_diff_slices(Algorithm::Myers, &ojt_keys, &jp_keys) {
+        let ojt = &ojt_moras[op.old_range()];
+        let jp = &jp_moras[op.new_range()];
+        let diff = match op {
+            similar::DiffOp::Equal { .. } => DiffKind::None,
+            _ => worst_diff(ojt, ojt_phonemes)
+                .max(worst_diff(jp, jp_phonemes))
+                .max(DiffKind::Light),
+        };
+        segments.push(MoraSegment {
+            openjtalk: kana(ojt),
+            jpreprocess: kana(jp),
+            diff,
+        });
+    }
+    segments
+}
+
+// Equal moras as is, differing ones as "[OpenJTalk's/jpreprocess's]".
+pub fn format_mora_diff(segments: &[MoraSegment]) -> String {
+    segments
+        .iter()
+        .map(|segment| match segment.diff {
+            DiffKind::None => segment.openjtalk.clone(),
+            DiffKind::Light => format!(
+                "\x1b[33m[{}/{}]\x1b[0m",
+                segment.openjtalk, segment.jpreprocess
+            ),
+            DiffKind::Fatal => format!(
+                "\x1b[31m[{}/{}]\x1b[0m",
+                segment.openjtalk, segment.jpreprocess
+            ),
+        })
+        .collect()
+}
@@ -0,0 +1,160 @@
+use open_jtalk::{AsRawPtr, Njd};
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+// One NJD node in the shape printed by OpenJTalk's NJDNode_print (and jpreprocess's Display impl):
+// string,pos,pos_group1,pos_group2,pos_group3,ctype,cform,orig,read,pron,acc/mora_size,chain_rule,chain_flag
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NjdNode {
+    pub string: String,
+    pub pos: String,
+    pub ctype: String,
+    pub cform: String,
+    pub orig: String,
+    pub read: String,
+    pub pron: String,
+    pub acc: i32,
+    pub mora_size: i32,
+    pub chain_rule: String,
+    pub chain_flag: i32,
+}
+
+impl NjdNode {
+    // Parse from the right, since the surface string itself may contain commas.
+    pub fn from_feature(feature: &str) -> anyhow::Result<Self> {
+        let mut fields = feature.rsplitn(13, ',').collect::<Vec<_>>();
+        anyhow::ensure!(fields.len() == 13, "malformed NJD feature: {}", feature);
+        fields.reverse();
+
+        let (acc, mora_size) = fields[10]
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("malformed NJD accent: {}", feature))?;
+        Ok(Self {
+            string: fields[0].to_string(),
+            pos: fields[1..5].join(","),
+            ctype: fields[5].to_string(),
+            cform: fields[6].to_string(),
+            orig: fields[7].to_string(),
+            read: fields[8].to_string(),
+            pron: fields[9].to_string(),
+            acc: acc.parse()?,
+            mora_size: mora_size.parse()?,
+            chain_rule: fields[11].to_string(),
+            chain_flag: fields[12].parse()?,
+        })
+    }
+}
+
+impl std::fmt::Display for NjdNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}/{},{},{}",
+            self.string,
+            self.pos,
+            self.ctype,
+            self.cform,
+            self.orig,
+            self.read,
+            self.pron,
+            self.acc,
+            self.mora_size,
+            self.chain_rule,
+            self.chain_flag
+        )
+    }
+}
+
+fn c_string(ptr: *mut c_char) -> String {
+    if ptr.is_null() {
+        "*".to_string()
+    } else {
+        // SAFETY: non-null NJDNode strings are NUL-terminated buffers owned by the node.
+        unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+// Walk OpenJTalk's NJD linked list.
+pub fn openjtalk_nodes(njd: &Njd) -> Vec<NjdNode> {
+    let mut nodes = vec![];
+    // SAFETY: the NJD belongs to this thread's OpenJTalk resources and is not modified while we walk it.
+    unsafe {
+        let mut node = (*njd.as_raw_ptr()).head;
+        while let Some(n) = node.as_ref() {
+            nodes.push(NjdNode {
+                string: c_string(n.string),
+                pos: [n.pos, n.pos_group1, n.pos_group2, n.pos_group3]
+                    .map(c_string)
+                    .join(","),
+                ctype: c_string(n.ctype),
+                cform: c_string(n.cform),
+                orig: c_string(n.orig),
+                read: c_string(n.read),
+                pron: c_string(n.pron),
+                acc: n.acc,
+                mora_size: n.mora_size,
+                chain_rule: c_string(n.chain_rule),
+                chain_flag: n.chain_flag,
+            });
+            node = n.next;
+        }
+    }
+    nodes
+}
+
+pub fn jpreprocess_nodes(njd: &jpreprocess_njd::NJD) -> anyhow::Result<Vec<NjdNode>> {
+    njd.nodes
+        .iter()
+        .map(|node| NjdNode::from_feature(&node.to_string()))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NjdNodePair {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub openjtalk: Option<NjdNode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jpreprocess: Option<NjdNode>,
+    pub equal: bool,
+}
+
+// Align the two node lists by surface string. Nodes inside a replaced block are paired up
+// in order, and whatever is left over on the longer side is reported alone.
+pub fn align_nodes(ojt: &[NjdNode], jp: &[NjdNode]) -> Vec<NjdNodePair> {
+    use similar::{capture_diff_slices, Algorithm};
+
+    let ojt_surfaces = ojt.iter().map(|n| n.string.as_str()).collect::<Vec<_>>();
+    let jp_surfaces = jp.iter().map(|n| n.string.as_str()).collect::<Vec<_>>();
+
+    let mut pairs = vec![];
+    for op in capture_diff_slices(Algorithm::Myers, &ojt_surfaces, &jp_surfaces) {
+        let (_, old_range, new_range) = op.as_tag_tuple();
+        let (ojt_block, jp_block) = (&ojt[old_range], &jp[new_range]);
+        for offset in 0..ojt_block.len().max(jp_block.len()) {
+            let (o, j) = (ojt_block.get(offset), jp_block.get(offset));
+            pairs.push(NjdNodePair {
+                equal: o.is_some() && o == j,
+                openjtalk: o.cloned(),
+                jpreprocess: j.cloned(),
+            });
+        }
+    }
+    pairs
+}
+
+pub fn format_node(node: Option<&NjdNode>) -> String {
+    node.map_or_else(|| "(none)".to_string(), |n| n.to_string())
+}
+
+pub fn format_node_pair(pair: &NjdNodePair) -> String {
+    format!(
+        "OpenJTalk {} / JPreprocess {}",
+        format_node(pair.openjtalk.as_ref()),
+        format_node(pair.jpreprocess.as_ref())
+    )
+}
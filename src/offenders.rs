@@ -0,0 +1,119 @@
+// Surface words whose NJD nodes the engines pronounce differently in fatal mismatches, with
+// both readings, how often and where. This is the list for dictionary maintainers.
+use crate::njd::{NjdNode, NjdNodePair};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingPair {
+    // NJD pronunciations; empty if the engine has no node there.
+    pub openjtalk: String,
+    pub jpreprocess: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OffendingWord {
+    pub word: String,
+    pub count: usize,
+    // Most frequent first.
+    pub readings: Vec<ReadingPair>,
+    pub files: Vec<String>,
+}
+
+#[derive(Default)]
+struct WordCounts {
+    count: usize,
+    readings: BTreeMap<(String, String), usize>,
+    files: BTreeSet<String>,
+}
+
+#[derive(Default)]
+pub struct OffendingWords {
+    words: BTreeMap<String, WordCounts>,
+}
+
+impl OffendingWords {
+    // Node pairs are as aligned by `njd::align_nodes`, so a word missing on one side comes from
+    // a segmentation difference. Nodes pronounced the same are not counted, even if they
+    // differ otherwise.
+    pub fn add(&mut self, file: &str, njd: &[NjdNodePair]) {
+        for pair in njd.iter().filter(|pair| !pair.equal) {
+            let pron = |node: Option<&NjdNode>| node.map(|n| n.pron.clone()).unwrap_or_default();
+            let openjtalk = pron(pair.openjtalk.as_ref());
+            let jpreprocess = pron(pair.jpreprocess.as_ref());
+            if openjtalk == jpreprocess {
+                continue;
+            }
+            let Some(word) = pair
+                .openjtalk
+                .as_ref()
+                .or(pair.jpreprocess.as_ref())
+                .map(|n| n.string.clone())
+            else {
+                continue;
+            };
+            let counts = self.words.entry(word).or_default();
+            counts.count += 1;
+            *counts.readings.entry((openjtalk, jpreprocess)).or_default() += 1;
+            counts.files.insert(file.to_string());
+        }
+    }
+
+    // The `limit` most frequent words.
+    pub fn top(self, limit: usize) -> Vec<OffendingWord> {
+        let mut words = self
+            .words
+            .into_iter()
+            .map(|(word, counts)| {
+                let mut readings = counts
+                    .readings
+                    .into_iter()
+                    .map(|((openjtalk, jpreprocess), count)| ReadingPair {
+                        openjtalk,
+                        jpreprocess,
+                        count,
+                    })
+                    .collect::<Vec<_>>();
+                readings.sort_by_key(|r| std::cmp::Reverse(r.count));
+                OffendingWord {
+                    word,
+                    count: counts.count,
+                    readings,
+                    files: counts.files.into_iter().collect(),
+                }
+            })
+            .collect::<Vec<_>>();
+        words.sort_by_key(|w| std::cmp::Reverse(w.count));
+        words.truncate(limit);
+        words
+    }
+}
+
+// e.g. "今日: キョー / コンニチ (12), キョー / キョウ (1) in kokoro.txt, wagahaiwa_nekodearu.txt"
+pub fn format_offending_word(word: &OffendingWord) -> String {
+    let reading = |pron: &str| {
+        if pron.is_empty() {
+            "-".to_string()
+        } else {
+            pron.to_string()
+        }
+    };
+    format!(
+        "{}: {} in {}",
+        word.word,
+        word.readings
+            .iter()
+            .map(|r| format!(
+                "{} / {} ({})",
+                reading(&r.openjtalk),
+                reading(&r.jpreprocess),
+                r.count
+            ))
+            .collect::<Vec<_>>()
+            .join(", "),
+        word.files.join(", ")
+    )
+}
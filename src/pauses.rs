@@ -0,0 +1,77 @@
+// Where each engine pauses, compared apart from what it pronounces. Positions are counted in
+// moras from the start of the sentence, so that they can be compared even where the
+// phonemes around them differ.
+use crate::label_groups;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PauseComparison {
+    // Moras before each pause.
+    pub openjtalk_pauses: Vec<usize>,
+    pub jpreprocess_pauses: Vec<usize>,
+    // Moras before each breath group but the first.
+    pub openjtalk_breath_groups: Vec<usize>,
+    pub jpreprocess_breath_groups: Vec<usize>,
+}
+
+// (pauses, breath group boundaries)
+fn positions(labels: &[jlabel::Label]) -> (Vec<usize>, Vec<usize>) {
+    let moras = label_groups::group_by(labels, label_groups::mora_key);
+    let pauses = labels
+        .iter()
+        .enumerate()
+        .filter(|(_, label)| label.phoneme.c.as_deref() == Some("pau"))
+        .map(|(index, _)| moras.iter().take_while(|(_, m)| m.end <= index).count())
+        .collect();
+    let breath_groups = moras
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| {
+            let ((previous, ..), _) = &pair[0];
+            let ((next, ..), _) = &pair[1];
+            previous != next
+        })
+        .map(|(index, _)| index + 1)
+        .collect();
+    (pauses, breath_groups)
+}
+
+pub fn compare(ojt_labels: &[jlabel::Label], jp_labels: &[jlabel::Label]) -> PauseComparison {
+    let (openjtalk_pauses, openjtalk_breath_groups) = positions(ojt_labels);
+    let (jpreprocess_pauses, jpreprocess_breath_groups) = positions(jp_labels);
+    PauseComparison {
+        openjtalk_pauses,
+        jpreprocess_pauses,
+        openjtalk_breath_groups,
+        jpreprocess_breath_groups,
+    }
+}
+
+impl PauseComparison {
+    pub fn differs(&self) -> bool {
+        self.openjtalk_pauses != self.jpreprocess_pauses
+            || self.openjtalk_breath_groups != self.jpreprocess_breath_groups
+    }
+}
+
+pub fn format_pauses(pauses: &PauseComparison) -> String {
+    let positions = |positions: &[usize]| {
+        if positions.is_empty() {
+            "none".to_string()
+        } else {
+            positions
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+    format!(
+        "after moras OpenJTalk {} / JPreprocess {} (breath groups OpenJTalk {} / JPreprocess {})",
+        positions(&pauses.openjtalk_pauses),
+        positions(&pauses.jpreprocess_pauses),
+        positions(&pauses.openjtalk_breath_groups),
+        positions(&pauses.jpreprocess_breath_groups)
+    )
+}
@@ -0,0 +1,103 @@
+// Pitch accent as each engine's labels give it: every accent phrase's accent type and the
+// high/low pitch of its moras. Sentences whose phonemes match can still be read with a
+// different accent, which is what matters most to a listener.
+use crate::accent::{self, AccentPhrase};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhrasePitch {
+    #[serde(flatten)]
+    pub phrase: AccentPhrase,
+    // One per mora: whether its pitch is high.
+    pub high: Vec<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PitchComparison {
+    pub openjtalk: Vec<PhrasePitch>,
+    pub jpreprocess: Vec<PhrasePitch>,
+}
+
+// Tokyo-style pitch: the first mora is low unless the accent falls right after it, and the
+// pitch stays high up to the accent nucleus. A flat phrase's nucleus is its last mora.
+fn pitch_pattern(moras: usize, accent: usize) -> Vec<bool> {
+    (1..=moras)
+        .map(|i| match accent {
+            1 => i == 1,
+            _ => i > 1 && i <= accent,
+        })
+        .collect()
+}
+
+fn phrase_pitches(labels: &[jlabel::Label]) -> Vec<PhrasePitch> {
+    accent::from_labels(labels)
+        .into_iter()
+        .map(|phrase| PhrasePitch {
+            high: pitch_pattern(phrase.moras, phrase.accent),
+            phrase,
+        })
+        .collect()
+}
+
+pub fn compare(ojt_labels: &[jlabel::Label], jp_labels: &[jlabel::Label]) -> PitchComparison {
+    PitchComparison {
+        openjtalk: phrase_pitches(ojt_labels),
+        jpreprocess: phrase_pitches(jp_labels),
+    }
+}
+
+impl PitchComparison {
+    // Whether the engines split the sentence into accent phrases differently or give a phrase
+    // a different accent type, i.e. whether the pitch contours differ.
+    pub fn differs(&self) -> bool {
+        let shape = |phrases: &[PhrasePitch]| {
+            phrases
+                .iter()
+                .map(|p| (p.phrase.moras, p.phrase.accent))
+                .collect::<Vec<_>>()
+        };
+        shape(&self.openjtalk) != shape(&self.jpreprocess)
+    }
+}
+
+// Accent phrases in the notation of `accent::format_phrases`, followed by the pitch of their
+// moras, e.g. "キョ'ーワ/ハレ (HLLL/LH)".
+pub fn format_pitch(phrases: &[PhrasePitch]) -> String {
+    let accent_phrases = phrases.iter().map(|p| p.phrase.clone()).collect::<Vec<_>>();
+    let pattern = phrases
+        .iter()
+        .map(|p| {
+            p.high
+                .iter()
+                .map(|&high| if high { 'H' } else { 'L' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{} ({})", accent::format_phrases(&accent_phrases), pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(moras: usize, accent: usize) -> String {
+        pitch_pattern(moras, accent)
+            .into_iter()
+            .map(|high| if high { 'H' } else { 'L' })
+            .collect()
+    }
+
+    #[test]
+    fn pitch_pattern_by_accent_type() {
+        // Atamadaka, nakadaka, odaka and flat (accent at the last mora).
+        assert_eq!(pattern(3, 1), "HLL");
+        assert_eq!(pattern(4, 2), "LHLL");
+        assert_eq!(pattern(4, 3), "LHHL");
+        assert_eq!(pattern(3, 3), "LHH");
+        assert_eq!(pattern(1, 1), "H");
+        assert_eq!(pattern(0, 0), "");
+    }
+}
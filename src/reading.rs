@@ -0,0 +1,87 @@
+// Mora-level scoring of both engines against a reference reading.
+use crate::mora;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingScore {
+    pub reference: String,
+    // Each engine's reading, converted back to kana from its labels.
+    pub openjtalk: String,
+    pub jpreprocess: String,
+    pub moras: usize,
+    pub openjtalk_correct: usize,
+    pub jpreprocess_correct: usize,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct ReadingCounts {
+    pub sentences: usize,
+    pub moras: usize,
+    pub openjtalk_correct: usize,
+    pub jpreprocess_correct: usize,
+}
+
+impl ReadingCounts {
+    pub fn openjtalk_accuracy(&self) -> f64 {
+        crate::ratio(self.openjtalk_correct, self.moras)
+    }
+
+    pub fn jpreprocess_accuracy(&self) -> f64 {
+        crate::ratio(self.jpreprocess_correct, self.moras)
+    }
+
+    pub fn add_score(&mut self, score: &ReadingScore) {
+        self.sentences += 1;
+        self.moras += score.moras;
+        self.openjtalk_correct += score.openjtalk_correct;
+        self.jpreprocess_correct += score.jpreprocess_correct;
+    }
+
+    pub fn add(&mut self, other: ReadingCounts) {
+        self.sentences += other.sentences;
+        self.moras += other.moras;
+        self.openjtalk_correct += other.openjtalk_correct;
+        self.jpreprocess_correct += other.jpreprocess_correct;
+    }
+}
+
+// Reference moras that the aligned engine reading reproduces.
+fn correct_moras(reference: &[String], moras: &[String]) -> usize {
+    use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+    capture_diff_slices(Algorithm::Myers, reference, moras)
+        .iter()
+        .map(|op| match op {
+            DiffOp::Equal { len, .. } => *len,
+            _ => 0,
+        })
+        .sum()
+}
+
+pub fn score(reference: &str, ojt_phonemes: &[String], jp_phonemes: &[String]) -> ReadingScore {
+    let reference_moras = mora::kana_to_moras(reference);
+    let ojt_moras = mora::phonemes_to_moras(ojt_phonemes);
+    let jp_moras = mora::phonemes_to_moras(jp_phonemes);
+    ReadingScore {
+        reference: reference.to_string(),
+        openjtalk: mora::moras_to_kana(&ojt_moras),
+        jpreprocess: mora::moras_to_kana(&jp_moras),
+        moras: reference_moras.len(),
+        openjtalk_correct: correct_moras(&reference_moras, &ojt_moras),
+        jpreprocess_correct: correct_moras(&reference_moras, &jp_moras),
+    }
+}
+
+pub fn format_reading(score: &ReadingScore) -> String {
+    format!(
+        "{} (OpenJTalk {} {}/{}, JPreprocess {} {}/{})",
+        score.reference,
+        score.openjtalk,
+        score.openjtalk_correct,
+        score.moras,
+        score.jpreprocess,
+        score.jpreprocess_correct,
+        score.moras
+    )
+}
@@ -0,0 +1,219 @@
+// Scores of both engines against whatever reference a corpus file carries for a sentence:
+// a reading (ROHAN4600, VOICEVOX projects), accent phrases (VOICEVOX projects), ruby (Aozora
+// Bunko) or a hand-corrected pronunciation (gold files).
+use crate::accent::{self, AccentCounts, AccentScore};
+use crate::corpus::Sentence;
+use crate::gold::{self, GoldCounts, GoldScore};
+use crate::njd::NjdNode;
+use crate::reading::{self, ReadingCounts, ReadingScore};
+use crate::ruby::{self, RubyCounts, RubyScore};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct References {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<ReadingScore>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent: Option<AccentScore>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ruby: Option<RubyScore>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gold: Option<GoldScore>,
+}
+
+// The engines' output for one sentence. `final_nodes` is only needed for sentences with ruby.
+pub struct EngineOutput<'a> {
+    pub ojt_labels: &'a [jlabel::Label],
+    pub jp_labels: &'a [jlabel::Label],
+    pub ojt_phonemes: &'a [String],
+    pub jp_phonemes: &'a [String],
+    pub final_nodes: Option<(&'a [NjdNode], &'a [NjdNode])>,
+}
+
+impl References {
+    pub fn score(sentence: &Sentence, output: EngineOutput) -> Self {
+        Self {
+            reading: sentence
+                .reading
+                .as_ref()
+                .map(|reading| reading::score(reading, output.ojt_phonemes, output.jp_phonemes)),
+            accent: sentence
+                .accent_phrases
+                .as_ref()
+                .map(|reference| accent::score(reference, output.ojt_labels, output.jp_labels)),
+            ruby: output
+                .final_nodes
+                .filter(|_| !sentence.ruby.is_empty())
+                .map(|(ojt_nodes, jp_nodes)| {
+                    ruby::score(&sentence.text, &sentence.ruby, ojt_nodes, jp_nodes)
+                }),
+            gold: sentence
+                .pronunciation
+                .as_ref()
+                .map(|reference| gold::score(reference, output.ojt_phonemes, output.jp_phonemes)),
+        }
+    }
+
+    // Lines for a mismatch report.
+    pub fn report(&self, report: &mut Vec<String>) {
+        if let Some(reading) = &self.reading {
+            report.push(format!(
+                "      Reading: {}",
+                reading::format_reading(reading)
+            ));
+        }
+        if let Some(accent) = &self.accent {
+            report.push(format!("       Accent: {}", accent::format_accent(accent)));
+        }
+        if let Some(ruby) = self.ruby.as_ref().filter(|r| !r.mismatches.is_empty()) {
+            report.push(format!("         Ruby: {}", ruby::format_ruby(ruby)));
+        }
+        if let Some(gold) = &self.gold {
+            report.push(format!("         Gold: {}", gold::format_gold(gold)));
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct ReferenceCounts {
+    pub reading: ReadingCounts,
+    pub accent: AccentCounts,
+    pub ruby: RubyCounts,
+    pub gold: GoldCounts,
+    // Sentences with a reference that an engine failed on, left out of all of the above.
+    pub unscored_sentences: usize,
+}
+
+// The `Stats` fields for the references. All zero for a corpus without any.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReferenceStats {
+    // Sentences with a reference reading, and each engine's mora accuracy on them.
+    pub reading_sentences: usize,
+    pub openjtalk_mora_accuracy: f64,
+    pub jpreprocess_mora_accuracy: f64,
+    // Sentences with reference accent phrases: the share of reference phrases each engine
+    // reproduces (span and accent position), and the F1 of its phrase boundaries.
+    pub accent_sentences: usize,
+    pub openjtalk_accent_accuracy: f64,
+    pub jpreprocess_accent_accuracy: f64,
+    pub openjtalk_accent_boundary_f1: f64,
+    pub jpreprocess_accent_boundary_f1: f64,
+    // Ruby spans in Aozora Bunko texts, and how many of them each engine reads as annotated.
+    pub ruby_spans: usize,
+    pub openjtalk_ruby_accuracy: f64,
+    pub jpreprocess_ruby_accuracy: f64,
+    // Sentences from gold files: error rates against the gold pronunciation, and the share
+    // of sentences pronounced exactly as the gold one.
+    pub gold_sentences: usize,
+    pub openjtalk_phoneme_error_rate: f64,
+    pub jpreprocess_phoneme_error_rate: f64,
+    pub openjtalk_mora_error_rate: f64,
+    pub jpreprocess_mora_error_rate: f64,
+    pub openjtalk_exact_match_rate: f64,
+    pub jpreprocess_exact_match_rate: f64,
+    // Sentences with a reference left out of the scores because an engine failed on them.
+    pub unscored_sentences: usize,
+}
+
+impl ReferenceCounts {
+    pub fn add_references(&mut self, references: &References) {
+        if let Some(reading) = &references.reading {
+            self.reading.add_score(reading);
+        }
+        if let Some(accent) = &references.accent {
+            self.accent.add_score(accent);
+        }
+        if let Some(ruby) = &references.ruby {
+            self.ruby.add_score(ruby);
+        }
+        if let Some(gold) = &references.gold {
+            self.gold.add_score(gold);
+        }
+    }
+
+    pub fn add(&mut self, other: ReferenceCounts) {
+        self.reading.add(other.reading);
+        self.accent.add(other.accent);
+        self.ruby.add(other.ruby);
+        self.gold.add(other.gold);
+        self.unscored_sentences += other.unscored_sentences;
+    }
+
+    pub fn stats(&self) -> ReferenceStats {
+        let gold = &self.gold;
+        ReferenceStats {
+            reading_sentences: self.reading.sentences,
+            openjtalk_mora_accuracy: self.reading.openjtalk_accuracy(),
+            jpreprocess_mora_accuracy: self.reading.jpreprocess_accuracy(),
+            accent_sentences: self.accent.sentences,
+            openjtalk_accent_accuracy: self.accent.openjtalk_accuracy(),
+            jpreprocess_accent_accuracy: self.accent.jpreprocess_accuracy(),
+            openjtalk_accent_boundary_f1: self.accent.openjtalk_boundaries.f1(),
+            jpreprocess_accent_boundary_f1: self.accent.jpreprocess_boundaries.f1(),
+            ruby_spans: self.ruby.spans,
+            openjtalk_ruby_accuracy: self.ruby.openjtalk_accuracy(),
+            jpreprocess_ruby_accuracy: self.ruby.jpreprocess_accuracy(),
+            gold_sentences: gold.sentences,
+            openjtalk_phoneme_error_rate: gold.phoneme_error_rate(&gold.openjtalk),
+            jpreprocess_phoneme_error_rate: gold.phoneme_error_rate(&gold.jpreprocess),
+            openjtalk_mora_error_rate: gold.mora_error_rate(&gold.openjtalk),
+            jpreprocess_mora_error_rate: gold.mora_error_rate(&gold.jpreprocess),
+            openjtalk_exact_match_rate: gold.exact_match_rate(&gold.openjtalk),
+            jpreprocess_exact_match_rate: gold.exact_match_rate(&gold.jpreprocess),
+            unscored_sentences: self.unscored_sentences,
+        }
+    }
+
+    // Appended to the summary lines; empty unless the corpus has references.
+    pub fn format(&self) -> String {
+        let mut text = String::new();
+        if self.reading.sentences > 0 {
+            text += &format!(
+                ", reading accuracy over {} sentences: OpenJTalk {:.2}%, JPreprocess {:.2}%",
+                self.reading.sentences,
+                self.reading.openjtalk_accuracy() * 100.0,
+                self.reading.jpreprocess_accuracy() * 100.0
+            );
+        }
+        if self.accent.sentences > 0 {
+            text += &format!(
+                ", accent phrases over {} sentences: OpenJTalk {:.2}% (boundary F1 {:.3}), JPreprocess {:.2}% (boundary F1 {:.3})",
+                self.accent.sentences,
+                self.accent.openjtalk_accuracy() * 100.0,
+                self.accent.openjtalk_boundaries.f1(),
+                self.accent.jpreprocess_accuracy() * 100.0,
+                self.accent.jpreprocess_boundaries.f1()
+            );
+        }
+        if self.ruby.spans > 0 {
+            text += &format!(
+                ", ruby accuracy over {} spans: OpenJTalk {:.2}%, JPreprocess {:.2}%",
+                self.ruby.spans,
+                self.ruby.openjtalk_accuracy() * 100.0,
+                self.ruby.jpreprocess_accuracy() * 100.0
+            );
+        }
+        let gold = &self.gold;
+        if gold.sentences > 0 {
+            text += &format!(
+                ", gold over {} sentences: OpenJTalk PER {:.3} / MER {:.3} / exact {:.2}%, JPreprocess PER {:.3} / MER {:.3} / exact {:.2}%",
+                gold.sentences,
+                gold.phoneme_error_rate(&gold.openjtalk),
+                gold.mora_error_rate(&gold.openjtalk),
+                gold.exact_match_rate(&gold.openjtalk) * 100.0,
+                gold.phoneme_error_rate(&gold.jpreprocess),
+                gold.mora_error_rate(&gold.jpreprocess),
+                gold.exact_match_rate(&gold.jpreprocess) * 100.0
+            );
+        }
+        if self.unscored_sentences > 0 {
+            text += &format!(
+                ", {} sentences with references left unscored after an engine error",
+                self.unscored_sentences
+            );
+        }
+        text
+    }
+}
@@ -0,0 +1,136 @@
+// Ruby from Aozora Bunko texts: does each engine read the annotated span as the ruby says?
+use crate::corpus::Ruby;
+use crate::mora;
+use crate::njd::NjdNode;
+use serde::{Deserialize, Serialize};
+
+// A ruby span that at least one engine reads differently.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RubyMismatch {
+    pub base: String,
+    pub ruby: String,
+    pub openjtalk: String,
+    pub jpreprocess: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RubyScore {
+    // Spans both engines' NJD could be lined up with.
+    pub spans: usize,
+    pub openjtalk_correct: usize,
+    pub jpreprocess_correct: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<RubyMismatch>,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct RubyCounts {
+    pub spans: usize,
+    pub openjtalk_correct: usize,
+    pub jpreprocess_correct: usize,
+}
+
+impl RubyCounts {
+    pub fn openjtalk_accuracy(&self) -> f64 {
+        crate::ratio(self.openjtalk_correct, self.spans)
+    }
+
+    pub fn jpreprocess_accuracy(&self) -> f64 {
+        crate::ratio(self.jpreprocess_correct, self.spans)
+    }
+
+    pub fn add_score(&mut self, score: &RubyScore) {
+        self.spans += score.spans;
+        self.openjtalk_correct += score.openjtalk_correct;
+        self.jpreprocess_correct += score.jpreprocess_correct;
+    }
+
+    pub fn add(&mut self, other: RubyCounts) {
+        self.spans += other.spans;
+        self.openjtalk_correct += other.openjtalk_correct;
+        self.jpreprocess_correct += other.jpreprocess_correct;
+    }
+}
+
+// The moras an engine reads `text[start..end]` as, from the `read` of the NJD nodes covering it.
+// Parts of those nodes outside the span have to be kana (typically okurigana), so that their
+// reading can be cut off; otherwise the span can't be scored.
+fn span_reading(text: &[char], nodes: &[NjdNode], start: usize, end: usize) -> Option<Vec<String>> {
+    let mut moras = vec![];
+    let mut offset = 0;
+    for node in nodes {
+        let node_start = offset;
+        offset += node.string.chars().count();
+        if offset <= start || node_start >= end {
+            continue;
+        }
+        let mut node_moras = mora::kana_to_moras(&node.read);
+        if node_start < start {
+            let before = mora::kana_to_moras(&text[node_start..start].iter().collect::<String>());
+            node_moras = node_moras.strip_prefix(before.as_slice())?.to_vec();
+        }
+        if offset > end {
+            let after = mora::kana_to_moras(&text[end..offset].iter().collect::<String>());
+            node_moras = node_moras.strip_suffix(after.as_slice())?.to_vec();
+        }
+        moras.extend(node_moras);
+    }
+    Some(moras)
+}
+
+pub fn score(text: &str, ruby: &[Ruby], ojt_nodes: &[NjdNode], jp_nodes: &[NjdNode]) -> RubyScore {
+    let chars = text.chars().collect::<Vec<_>>();
+    // Offsets are only meaningful if the nodes spell out the sentence as is.
+    let spells_text =
+        |nodes: &[NjdNode]| nodes.iter().map(|n| n.string.as_str()).collect::<String>() == text;
+    let (ojt_aligned, jp_aligned) = (spells_text(ojt_nodes), spells_text(jp_nodes));
+
+    let mut score = RubyScore {
+        spans: 0,
+        openjtalk_correct: 0,
+        jpreprocess_correct: 0,
+        mismatches: vec![],
+    };
+    for span in ruby {
+        let reading = |aligned: bool, nodes: &[NjdNode]| {
+            aligned
+                .then(|| span_reading(&chars, nodes, span.start, span.end))
+                .flatten()
+        };
+        let (Some(ojt), Some(jp)) = (
+            reading(ojt_aligned, ojt_nodes),
+            reading(jp_aligned, jp_nodes),
+        ) else {
+            continue;
+        };
+        let expected = mora::kana_to_moras(&span.reading);
+        score.spans += 1;
+        score.openjtalk_correct += (ojt == expected) as usize;
+        score.jpreprocess_correct += (jp == expected) as usize;
+        if ojt != expected || jp != expected {
+            score.mismatches.push(RubyMismatch {
+                base: chars[span.start..span.end].iter().collect(),
+                ruby: span.reading.clone(),
+                openjtalk: mora::moras_to_kana(&ojt),
+                jpreprocess: mora::moras_to_kana(&jp),
+            });
+        }
+    }
+    score
+}
+
+pub fn format_ruby(score: &RubyScore) -> String {
+    score
+        .mismatches
+        .iter()
+        .map(|m| {
+            format!(
+                "{}《{}》 (OpenJTalk {}, JPreprocess {})",
+                m.base, m.ruby, m.openjtalk, m.jpreprocess
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,421 @@
+// `diff <baseline.json> <current.json>`: what changed between two comparison runs,
+// e.g. before and after a jpreprocess change.
+use crate::{Entry, Results, Stats};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultsDiff {
+    pub baseline_commit: String,
+    pub commit: String,
+    pub totals: StatsDelta,
+    pub files: Vec<FileDiff>,
+    // Files only one of the runs has; they are not diffed.
+    pub added_files: Vec<String>,
+    pub removed_files: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+    pub file: String,
+    pub stats: StatsDelta,
+    // Mismatches and errors that are matches now.
+    pub fixed: Vec<KindChange>,
+    // Matches that are mismatches or errors now, unless the new difference is expected.
+    pub broken: Vec<KindChange>,
+    // Sentences that went from one kind of mismatch or error to another.
+    pub changed: Vec<KindChange>,
+    // Sentences only in the current run, and only in the baseline.
+    pub added: Vec<UnpairedSentence>,
+    pub removed: Vec<UnpairedSentence>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KindChange {
+    pub original: String,
+    pub baseline_index: usize,
+    pub index: usize,
+    pub baseline_kind: String,
+    pub kind: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnpairedSentence {
+    pub original: String,
+    pub index: usize,
+    pub kind: String,
+}
+
+impl UnpairedSentence {
+    fn new(entry: &Entry) -> Self {
+        Self {
+            original: entry.original().to_string(),
+            index: entry.index(),
+            kind: entry.kind().to_string(),
+        }
+    }
+}
+
+// Current minus baseline.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsDelta {
+    pub total: i64,
+    pub matches: i64,
+    pub light_mismatches: i64,
+    pub fatal_mismatches: i64,
+    pub label_mismatches: i64,
+    pub accent_mismatches: i64,
+    pub expected: i64,
+    pub segmentation_mismatches: i64,
+    pub segmentation_f1: f64,
+    pub pause_mismatches: i64,
+    pub utterance_mismatches: i64,
+    pub word_mismatches: i64,
+    pub phoneme_error_rate: f64,
+    pub mora_error_rate: f64,
+    pub jp_errors: i64,
+    pub ojt_errors: i64,
+}
+
+impl StatsDelta {
+    fn new(baseline: &Stats, current: &Stats) -> Self {
+        let delta = |baseline: usize, current: usize| current as i64 - baseline as i64;
+        Self {
+            total: delta(baseline.total, current.total),
+            matches: delta(baseline.matches, current.matches),
+            light_mismatches: delta(baseline.light_mismatches, current.light_mismatches),
+            fatal_mismatches: delta(baseline.fatal_mismatches, current.fatal_mismatches),
+            label_mismatches: delta(baseline.label_mismatches, current.label_mismatches),
+            accent_mismatches: delta(baseline.accent_mismatches, current.accent_mismatches),
+            expected: delta(baseline.expected, current.expected),
+            segmentation_mismatches: delta(
+                baseline.segmentation_mismatches,
+                current.segmentation_mismatches,
+            ),
+            segmentation_f1: current.segmentation_f1 - baseline.segmentation_f1,
+            pause_mismatches: delta(baseline.pause_mismatches, current.pause_mismatches),
+            utterance_mismatches: delta(
+                baseline.utterance_mismatches,
+                current.utterance_mismatches,
+            ),
+            word_mismatches: delta(baseline.word_mismatches, current.word_mismatches),
+            phoneme_error_rate: current.edits.phoneme_error_rate
+                - baseline.edits.phoneme_error_rate,
+            mora_error_rate: current.edits.mora_error_rate - baseline.edits.mora_error_rate,
+            jp_errors: delta(baseline.jp_errors, current.jp_errors),
+            ojt_errors: delta(baseline.ojt_errors, current.ojt_errors),
+        }
+    }
+}
+
+// Sentences are paired by their text rather than their index, so that edits to a corpus file
+// only affect the sentences that were edited. Repeated sentences are paired in order.
+fn diff_entries(file: &str, baseline: &[Entry], current: &[Entry], stats: StatsDelta) -> FileDiff {
+    let mut baseline_by_original: HashMap<&str, VecDeque<&Entry>> = HashMap::new();
+    for entry in baseline {
+        baseline_by_original
+            .entry(entry.original())
+            .or_default()
+            .push_back(entry);
+    }
+
+    let mut file_diff = FileDiff {
+        file: file.to_string(),
+        stats,
+        fixed: vec![],
+        broken: vec![],
+        changed: vec![],
+        added: vec![],
+        removed: vec![],
+    };
+    for entry in current {
+        let Some(baseline_entry) = baseline_by_original
+            .get_mut(entry.original())
+            .and_then(VecDeque::pop_front)
+        else {
+            file_diff.added.push(UnpairedSentence::new(entry));
+            continue;
+        };
+        if baseline_entry.kind() == entry.kind() {
+            continue;
+        }
+        let change = KindChange {
+            original: entry.original().to_string(),
+            baseline_index: baseline_entry.index(),
+            index: entry.index(),
+            baseline_kind: baseline_entry.kind().to_string(),
+            kind: entry.kind().to_string(),
+        };
+        if matches!(entry, Entry::Match(_)) {
+            file_diff.fixed.push(change);
+        } else if matches!(baseline_entry, Entry::Match(_)) && !matches!(entry, Entry::Expected(_))
+        {
+            file_diff.broken.push(change);
+        } else {
+            file_diff.changed.push(change);
+        }
+    }
+    file_diff.removed = baseline_by_original
+        .into_values()
+        .flatten()
+        .map(UnpairedSentence::new)
+        .collect();
+    file_diff.removed.sort_by_key(|sentence| sentence.index);
+    file_diff
+}
+
+pub fn diff_results(baseline: &Results, current: &Results) -> ResultsDiff {
+    let baseline_files = baseline
+        .files
+        .iter()
+        .map(|f| (f.file.as_str(), f))
+        .collect::<BTreeMap<_, _>>();
+    let mut files = vec![];
+    let mut added_files = vec![];
+    for current_file in &current.files {
+        match baseline_files.get(current_file.file.as_str()) {
+            Some(baseline_file) => files.push(diff_entries(
+                &current_file.file,
+                &baseline_file.entries,
+                &current_file.entries,
+                StatsDelta::new(&baseline_file.stats, &current_file.stats),
+            )),
+            None => added_files.push(current_file.file.clone()),
+        }
+    }
+    let current_files = current
+        .files
+        .iter()
+        .map(|f| f.file.as_str())
+        .collect::<BTreeSet<_>>();
+    let removed_files = baseline_files
+        .into_keys()
+        .filter(|file| !current_files.contains(file))
+        .map(str::to_string)
+        .collect();
+    ResultsDiff {
+        baseline_commit: baseline.commit.clone(),
+        commit: current.commit.clone(),
+        totals: StatsDelta::new(&baseline.totals, &current.totals),
+        files,
+        added_files,
+        removed_files,
+    }
+}
+
+fn format_delta(delta: &StatsDelta) -> String {
+    format!(
+        "matches {:+}, light {:+}, fatal {:+}, label {:+}, accent {:+}, expected {:+}, segmentation {:+} (F1 {:+.3}), pauses {:+}, utterance {:+}, word tags {:+}, PER {:+.4}, MER {:+.4}, jpreprocess errors {:+}, open_jtalk errors {:+}",
+        delta.matches,
+        delta.light_mismatches,
+        delta.fatal_mismatches,
+        delta.label_mismatches,
+        delta.accent_mismatches,
+        delta.expected,
+        delta.segmentation_mismatches,
+        delta.segmentation_f1,
+        delta.pause_mismatches,
+        delta.utterance_mismatches,
+        delta.word_mismatches,
+        delta.phoneme_error_rate,
+        delta.mora_error_rate,
+        delta.jp_errors,
+        delta.ojt_errors
+    )
+}
+
+pub fn load_results(path: &str) -> anyhow::Result<Results> {
+    let json = std::fs::read_to_string(path)?;
+    serde_json::from_str(&json).map_err(|e| anyhow::anyhow!("{}: {}", path, e))
+}
+
+pub fn run(args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    // Parse <baseline> <current> and --json <path> from args
+    let mut json_path: Option<String> = None;
+    let mut paths: Vec<String> = vec![];
+    let mut iter = args;
+    while let Some(arg) = iter.next() {
+        if arg == "--json" {
+            json_path = iter.next();
+        } else {
+            paths.push(arg);
+        }
+    }
+    let [baseline_path, current_path] = paths.as_slice() else {
+        anyhow::bail!("usage: diff <baseline.json> <current.json> [--json <path>]");
+    };
+
+    let results_diff = diff_results(&load_results(baseline_path)?, &load_results(current_path)?);
+
+    for file_diff in &results_diff.files {
+        println!(
+            "{}: \x1b[32m{} fixed\x1b[0m, \x1b[31m{} broken\x1b[0m, \x1b[33m{} changed\x1b[0m, {} added, {} removed ({})",
+            file_diff.file,
+            file_diff.fixed.len(),
+            file_diff.broken.len(),
+            file_diff.changed.len(),
+            file_diff.added.len(),
+            file_diff.removed.len(),
+            format_delta(&file_diff.stats)
+        );
+        for (label, changes) in [
+            ("\x1b[32m        Fixed:\x1b[0m", &file_diff.fixed),
+            ("\x1b[31m       Broken:\x1b[0m", &file_diff.broken),
+            ("\x1b[33m      Changed:\x1b[0m", &file_diff.changed),
+        ] {
+            for change in changes {
+                println!(
+                    "{} [{}] {} ({} -> {})",
+                    label,
+                    change.index + 1,
+                    change.original,
+                    change.baseline_kind,
+                    change.kind
+                );
+            }
+        }
+        for (label, sentences) in [
+            ("        Added:", &file_diff.added),
+            ("      Removed:", &file_diff.removed),
+        ] {
+            for sentence in sentences {
+                println!(
+                    "{} [{}] {} ({})",
+                    label,
+                    sentence.index + 1,
+                    sentence.original,
+                    sentence.kind
+                );
+            }
+        }
+    }
+    for file in &results_diff.added_files {
+        println!("{}: only in the current run, not compared", file);
+    }
+    for file in &results_diff.removed_files {
+        println!("{}: only in the baseline, not compared", file);
+    }
+
+    println!();
+    println!(
+        "Total ({} -> {}): \x1b[32m{} fixed\x1b[0m, \x1b[31m{} broken\x1b[0m, \x1b[33m{} changed\x1b[0m, {} added, {} removed ({})",
+        results_diff.baseline_commit,
+        results_diff.commit,
+        results_diff.files.iter().map(|f| f.fixed.len()).sum::<usize>(),
+        results_diff.files.iter().map(|f| f.broken.len()).sum::<usize>(),
+        results_diff.files.iter().map(|f| f.changed.len()).sum::<usize>(),
+        results_diff.files.iter().map(|f| f.added.len()).sum::<usize>(),
+        results_diff.files.iter().map(|f| f.removed.len()).sum::<usize>(),
+        format_delta(&results_diff.totals)
+    );
+
+    if let Some(path) = json_path {
+        let json = serde_json::to_string(&results_diff)?;
+        std::fs::write(&path, json)?;
+        eprintln!("JSON written to {}", path);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: &str, index: usize, original: &str) -> Entry {
+        let entry = serde_json::json!({
+            "kind": kind,
+            "index": index,
+            "original": original,
+            "openjtalk": [],
+            "jpreprocess": [],
+        });
+        serde_json::from_value(entry).unwrap()
+    }
+
+    fn expected(index: usize, original: &str, actual: Entry) -> Entry {
+        let entry = serde_json::json!({
+            "kind": "expected",
+            "index": index,
+            "original": original,
+            "actual": actual,
+        });
+        serde_json::from_value(entry).unwrap()
+    }
+
+    fn diff(baseline: &[Entry], current: &[Entry]) -> FileDiff {
+        let stats = StatsDelta::new(&Stats::default(), &Stats::default());
+        diff_entries("a.txt", baseline, current, stats)
+    }
+
+    fn changes(changes: &[KindChange]) -> Vec<(usize, usize, &str, &str)> {
+        changes
+            .iter()
+            .map(|c| {
+                (
+                    c.baseline_index,
+                    c.index,
+                    c.baseline_kind.as_str(),
+                    c.kind.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn classifies_kind_changes() {
+        let baseline = [
+            entry("fatal", 0, "一"),
+            entry("match", 1, "二"),
+            entry("light", 2, "三"),
+            entry("match", 3, "四"),
+            entry("match", 4, "五"),
+        ];
+        let current = [
+            entry("match", 0, "一"),
+            entry("fatal", 1, "二"),
+            entry("jp_error", 2, "三"),
+            expected(3, "四", entry("light", 3, "四")),
+            entry("match", 4, "五"),
+        ];
+        let file_diff = diff(&baseline, &current);
+        assert_eq!(changes(&file_diff.fixed), [(0, 0, "fatal", "match")]);
+        assert_eq!(changes(&file_diff.broken), [(1, 1, "match", "fatal")]);
+        assert_eq!(
+            changes(&file_diff.changed),
+            [(2, 2, "light", "jp_error"), (3, 3, "match", "expected")]
+        );
+        assert!(file_diff.added.is_empty() && file_diff.removed.is_empty());
+    }
+
+    #[test]
+    fn pairs_repeated_sentences_in_order() {
+        // Repeated sentences pair up in order, even though a sentence was inserted.
+        let baseline = [
+            entry("match", 0, "はい"),
+            entry("fatal", 1, "はい"),
+            entry("match", 2, "消えた"),
+        ];
+        let current = [
+            entry("match", 0, "増えた"),
+            entry("match", 1, "はい"),
+            entry("match", 2, "はい"),
+        ];
+        let file_diff = diff(&baseline, &current);
+        assert_eq!(changes(&file_diff.fixed), [(1, 2, "fatal", "match")]);
+        assert!(file_diff.broken.is_empty() && file_diff.changed.is_empty());
+        let unpaired = |sentences: &[UnpairedSentence]| {
+            sentences
+                .iter()
+                .map(|s| (s.index, s.original.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(unpaired(&file_diff.added), [(0, "増えた".to_string())]);
+        assert_eq!(unpaired(&file_diff.removed), [(2, "消えた".to_string())]);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::stages::{Snapshots, Stage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Segmentation {
+    pub openjtalk: Vec<String>,
+    pub jpreprocess: Vec<String>,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+// Boundary counts of a predicted segmentation against a reference one. For tokens, OpenJTalk's
+// MeCab segmentation is the reference and jpreprocess's is predicted.
+#[derive(Default, Clone, Copy)]
+pub struct BoundaryCounts {
+    pub matched: usize,
+    pub reference: usize,
+    pub predicted: usize,
+}
+
+impl BoundaryCounts {
+    pub fn new(reference: &BTreeSet<usize>, predicted: &BTreeSet<usize>) -> Self {
+        Self {
+            matched: reference.intersection(predicted).count(),
+            reference: reference.len(),
+            predicted: predicted.len(),
+        }
+    }
+
+    pub fn precision(&self) -> f64 {
+        crate::ratio(self.matched, self.predicted)
+    }
+
+    pub fn recall(&self) -> f64 {
+        crate::ratio(self.matched, self.reference)
+    }
+
+    pub fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+
+    pub fn add(&mut self, other: BoundaryCounts) {
+        self.matched += other.matched;
+        self.reference += other.reference;
+        self.predicted += other.predicted;
+    }
+}
+
+// Surfaces of the NJD nodes right after mecab2njd, i.e. the tokenizer's segmentation.
+pub fn tokens(snapshots: &Snapshots) -> Vec<String> {
+    snapshots
+        .iter()
+        .find(|(stage, _)| *stage == Stage::Mecab2njd)
+        .map(|(_, nodes)| nodes.iter().map(|n| n.string.clone()).collect())
+        .unwrap_or_default()
+}
+
+// Offsets between consecutive segments of the given lengths (sentence start and end excluded).
+pub fn boundaries(lengths: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+    let mut offset = 0;
+    let mut boundaries = BTreeSet::new();
+    for length in lengths {
+        if offset > 0 {
+            boundaries.insert(offset);
+        }
+        offset += length;
+    }
+    boundaries
+}
+
+// Character offsets between tokens.
+fn token_boundaries(tokens: &[String]) -> BTreeSet<usize> {
+    boundaries(tokens.iter().map(|token| token.chars().count()))
+}
+
+// Boundary precision/recall of jpreprocess against MeCab. The token lists are only kept
+// (as `Segmentation`) when the two segmentations differ.
+pub fn compare(ojt: Vec<String>, jp: Vec<String>) -> (BoundaryCounts, Option<Segmentation>) {
+    let ojt_boundaries = token_boundaries(&ojt);
+    let jp_boundaries = token_boundaries(&jp);
+    let counts = BoundaryCounts::new(&ojt_boundaries, &jp_boundaries);
+    if ojt_boundaries == jp_boundaries {
+        return (counts, None);
+    }
+    let segmentation = Segmentation {
+        openjtalk: ojt,
+        jpreprocess: jp,
+        precision: counts.precision(),
+        recall: counts.recall(),
+        f1: counts.f1(),
+    };
+    (counts, Some(segmentation))
+}
+
+pub fn format_segmentation(segmentation: &Segmentation) -> String {
+    format!(
+        "OpenJTalk {} / JPreprocess {} (P {:.2}, R {:.2}, F1 {:.2})",
+        segmentation.openjtalk.join("|"),
+        segmentation.jpreprocess.join("|"),
+        segmentation.precision,
+        segmentation.recall,
+        segmentation.f1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(tokens: &[&str]) -> Vec<String> {
+        tokens.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn same_segmentation_is_not_kept() {
+        let (counts, segmentation) = compare(strings(&["今日", "は"]), strings(&["今日", "は"]));
+        assert_eq!(
+            (counts.matched, counts.reference, counts.predicted),
+            (1, 1, 1)
+        );
+        assert!(segmentation.is_none());
+    }
+
+    #[test]
+    fn split_token_lowers_precision_only() {
+        // 東京都|に vs 東京|都|に: boundaries {3} against {2, 3}.
+        let (counts, segmentation) =
+            compare(strings(&["東京都", "に"]), strings(&["東京", "都", "に"]));
+        assert_eq!(
+            (counts.matched, counts.reference, counts.predicted),
+            (1, 1, 2)
+        );
+        let segmentation = segmentation.unwrap();
+        assert_eq!(segmentation.precision, 0.5);
+        assert_eq!(segmentation.recall, 1.0);
+        assert!((segmentation.f1 - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_boundaries_score_zero() {
+        let (counts, segmentation) = compare(strings(&["猫"]), strings(&["猫"]));
+        assert_eq!(
+            (counts.precision(), counts.recall(), counts.f1()),
+            (0.0, 0.0, 0.0)
+        );
+        assert!(segmentation.is_none());
+    }
+}
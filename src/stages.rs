@@ -0,0 +1,174 @@
+use crate::njd::{self, NjdNode};
+use serde::{Deserialize, Serialize};
+
+// The NJD pipeline stages, in the order both engines run them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Mecab2njd,
+    SetPronunciation,
+    SetDigit,
+    SetAccentPhrase,
+    SetAccentType,
+    SetUnvoicedVowel,
+    SetLongVowel,
+    Njd2jpcommon,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Mecab2njd => "mecab2njd",
+            Stage::SetPronunciation => "set_pronunciation",
+            Stage::SetDigit => "set_digit",
+            Stage::SetAccentPhrase => "set_accent_phrase",
+            Stage::SetAccentType => "set_accent_type",
+            Stage::SetUnvoicedVowel => "set_unvoiced_vowel",
+            Stage::SetLongVowel => "set_long_vowel",
+            Stage::Njd2jpcommon => "njd2jpcommon",
+        }
+    }
+}
+
+pub type Snapshots = Vec<(Stage, Vec<NjdNode>)>;
+
+type JpStage = fn(&mut jpreprocess_njd::NJD);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageDivergence {
+    pub stage: Stage,
+    // First NJD node that differs; absent when the NJD agrees and only the labels differ.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub openjtalk: Option<NjdNode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jpreprocess: Option<NjdNode>,
+}
+
+// The NJD as handed to njd2jpcommon.
+pub fn final_nodes(snapshots: &Snapshots) -> &[NjdNode] {
+    snapshots.last().map_or(&[], |(_, nodes)| nodes)
+}
+
+// The labels of one engine run, and the NJD snapshots taken along the way. Snapshots that
+// could not be taken do not fail the labels.
+pub struct EngineRun {
+    pub labels: Vec<jlabel::Label>,
+    pub snapshots: anyhow::Result<Snapshots>,
+}
+
+// jpreprocess's preprocessing no longer ends in the NJD `jpreprocess_snapshots` ends in: a stage
+// was added, removed or reordered upstream. Every divergence report would be wrong, so this
+// ends the comparison instead of being recorded as a jpreprocess error.
+#[derive(Debug)]
+pub struct StagesOutdated;
+
+impl std::fmt::Display for StagesOutdated {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "jpreprocess's NJD preprocessing no longer matches stages::jpreprocess_snapshots"
+        )
+    }
+}
+
+impl std::error::Error for StagesOutdated {}
+
+// OpenJTalk's labels, with the NJD after mecab2njd and each set_* stage.
+pub fn run_openjtalk(text: &str) -> anyhow::Result<EngineRun> {
+    let mut snapshots = vec![];
+    let labels = crate::extract_fullcontext_with(text, |stage, ojt_njd| {
+        snapshots.push((stage, njd::openjtalk_nodes(ojt_njd)));
+    })?;
+    Ok(EngineRun {
+        labels,
+        snapshots: Ok(snapshots),
+    })
+}
+
+// jpreprocess's labels, from its own preprocessing of the NJD built from its tokenizer output,
+// with the same snapshots taken by running the stages one by one on a copy.
+pub fn run_jpreprocess(
+    text: &str,
+    text_to_njd: impl FnOnce(&str) -> anyhow::Result<jpreprocess_njd::NJD>,
+) -> anyhow::Result<EngineRun> {
+    let jp_njd = text_to_njd(text)?;
+    let mut preprocessed = jp_njd.clone();
+    preprocessed.preprocess();
+    let labels = jpreprocess_jpcommon::njdnodes_to_features(&preprocessed.nodes);
+    let snapshots = jpreprocess_snapshots(jp_njd);
+    if let (Ok(snapshots), Ok(nodes)) = (&snapshots, njd::jpreprocess_nodes(&preprocessed)) {
+        if final_nodes(snapshots) != nodes.as_slice() {
+            return Err(StagesOutdated.into());
+        }
+    }
+    Ok(EngineRun { labels, snapshots })
+}
+
+// NJD after mecab2njd and each set_* stage of jpreprocess's preprocessing.
+fn jpreprocess_snapshots(mut jp_njd: jpreprocess_njd::NJD) -> anyhow::Result<Snapshots> {
+    use jpreprocess_njd::*;
+
+    let mut snapshots = vec![(Stage::Mecab2njd, njd::jpreprocess_nodes(&jp_njd)?)];
+    let stages: [(Stage, JpStage); 6] = [
+        (
+            Stage::SetPronunciation,
+            pronunciation::njd_set_pronunciation,
+        ),
+        (Stage::SetDigit, digit::njd_set_digit),
+        (Stage::SetAccentPhrase, accent_phrase::njd_set_accent_phrase),
+        (Stage::SetAccentType, accent_type::njd_set_accent_type),
+        (
+            Stage::SetUnvoicedVowel,
+            unvoiced_vowel::njd_set_unvoiced_vowel,
+        ),
+        (Stage::SetLongVowel, long_vowel::njd_set_long_vowel),
+    ];
+    for (stage, run) in stages {
+        run(&mut jp_njd);
+        snapshots.push((stage, njd::jpreprocess_nodes(&jp_njd)?));
+    }
+    Ok(snapshots)
+}
+
+// The first stage after which the two NJD node lists disagree.
+// If every NJD stage agrees, the difference comes from label generation (njd2jpcommon).
+pub fn first_divergence(ojt: &Snapshots, jp: &Snapshots) -> StageDivergence {
+    for ((stage, ojt_nodes), (_, jp_nodes)) in ojt.iter().zip(jp) {
+        if ojt_nodes == jp_nodes {
+            continue;
+        }
+        let node_index = ojt_nodes
+            .iter()
+            .zip(jp_nodes)
+            .position(|(o, j)| o != j)
+            .unwrap_or(ojt_nodes.len().min(jp_nodes.len()));
+        return StageDivergence {
+            stage: *stage,
+            node_index: Some(node_index),
+            openjtalk: ojt_nodes.get(node_index).cloned(),
+            jpreprocess: jp_nodes.get(node_index).cloned(),
+        };
+    }
+    StageDivergence {
+        stage: Stage::Njd2jpcommon,
+        node_index: None,
+        openjtalk: None,
+        jpreprocess: None,
+    }
+}
+
+pub fn format_divergence(divergence: &StageDivergence) -> String {
+    match divergence.node_index {
+        Some(index) => format!(
+            "{} (node #{}: OpenJTalk {} / JPreprocess {})",
+            divergence.stage.name(),
+            index,
+            njd::format_node(divergence.openjtalk.as_ref()),
+            njd::format_node(divergence.jpreprocess.as_ref())
+        ),
+        None => divergence.stage.name().to_string(),
+    }
+}
@@ -0,0 +1,87 @@
+// Utterance-level features of each engine's labels: how many breath groups, accent phrases and
+// moras the sentence has, and which accent phrases end in a question. They show up as label
+// differences on every phoneme, so they are compared once per sentence instead.
+use crate::label_groups;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct UtteranceFeatures {
+    pub breath_groups: usize,
+    pub accent_phrases: usize,
+    pub moras: usize,
+    // 1-based accent phrases marked as interrogative.
+    pub interrogative: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UtteranceComparison {
+    pub openjtalk: UtteranceFeatures,
+    pub jpreprocess: UtteranceFeatures,
+}
+
+fn features(labels: &[jlabel::Label]) -> UtteranceFeatures {
+    let interrogative = label_groups::group_by(labels, label_groups::accent_phrase_key)
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, range))| {
+            labels[range.start]
+                .accent_phrase_curr
+                .as_ref()
+                .is_some_and(|accent_phrase| accent_phrase.is_interrogative)
+        })
+        .map(|(index, _)| index + 1)
+        .collect();
+    // Every label carries the same utterance counts; the first is a silence, which has them too.
+    let utterance = labels.first().map(|l| &l.utterance);
+    UtteranceFeatures {
+        breath_groups: utterance.map_or(0, |u| u.breath_group_count as usize),
+        accent_phrases: utterance.map_or(0, |u| u.accent_phrase_count as usize),
+        moras: utterance.map_or(0, |u| u.mora_count as usize),
+        interrogative,
+    }
+}
+
+pub fn compare(ojt_labels: &[jlabel::Label], jp_labels: &[jlabel::Label]) -> UtteranceComparison {
+    UtteranceComparison {
+        openjtalk: features(ojt_labels),
+        jpreprocess: features(jp_labels),
+    }
+}
+
+impl UtteranceComparison {
+    pub fn differs(&self) -> bool {
+        self.openjtalk != self.jpreprocess
+    }
+
+    pub fn interrogative_differs(&self) -> bool {
+        self.openjtalk.interrogative != self.jpreprocess.interrogative
+    }
+}
+
+fn format_features(features: &UtteranceFeatures) -> String {
+    let interrogative = if features.interrogative.is_empty() {
+        "none".to_string()
+    } else {
+        features
+            .interrogative
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "{} breath groups, {} accent phrases, {} moras, interrogative {}",
+        features.breath_groups, features.accent_phrases, features.moras, interrogative
+    )
+}
+
+// e.g. "OpenJTalk 1 breath groups, 3 accent phrases, 9 moras, interrogative 3 / JPreprocess ..."
+pub fn format_utterance(utterance: &UtteranceComparison) -> String {
+    format!(
+        "OpenJTalk {} / JPreprocess {}",
+        format_features(&utterance.openjtalk),
+        format_features(&utterance.jpreprocess)
+    )
+}
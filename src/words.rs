@@ -0,0 +1,178 @@
+// Word-level tags from the labels: part of speech, conjugation type and conjugation form.
+// Accent rules depend on the part of speech, so systematic tagging differences between the
+// engines show up here before they show up as accent mismatches.
+use crate::label_groups;
+use crate::mora;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// The part-of-speech IDs of OpenJTalk's jpcommon rules. Several parts of speech share an ID;
+// those are named by what they have in common.
+fn pos_name(id: u8) -> Option<&'static str> {
+    Some(match id {
+        1 => "形容詞",
+        2 => "名詞-普通名詞",
+        3 => "形状詞",
+        4 => "代名詞",
+        5 => "名詞-数詞",
+        6 => "副詞",
+        7 => "連体詞",
+        8 => "接続詞",
+        9 => "感動詞",
+        10 => "助動詞",
+        11 => "助詞-副助詞",
+        12 => "助詞-接続助詞",
+        13 => "助詞-格助詞",
+        14 => "接尾辞",
+        15 => "接尾辞-形状詞的",
+        16 => "接頭辞",
+        17 => "助詞-終助詞",
+        18 => "名詞-固有名詞",
+        19 => "名詞-非自立",
+        20 => "動詞",
+        21 => "動詞-非自立",
+        22 => "名詞-サ変接続",
+        23 => "助詞-その他",
+        24 => "助詞-係助詞",
+        25 => "フィラー",
+        _ => return None,
+    })
+}
+
+// Undefined values are "xx", as in the full-context label format.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WordTags {
+    pub pos: String,
+    pub ctype: String,
+    pub cform: String,
+}
+
+impl WordTags {
+    fn new(word: &jlabel::Word) -> Self {
+        let id = |id: Option<u8>| id.map_or_else(|| "xx".to_string(), |id| id.to_string());
+        Self {
+            pos: word.pos.map_or_else(
+                || "xx".to_string(),
+                |pos| pos_name(pos).map_or_else(|| pos.to_string(), str::to_string),
+            ),
+            ctype: id(word.ctype),
+            cform: id(word.cform),
+        }
+    }
+}
+
+// A word both engines pronounce the same but tag differently.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WordDiff {
+    pub kana: String,
+    pub openjtalk: WordTags,
+    pub jpreprocess: WordTags,
+}
+
+struct LabelWord {
+    phonemes: Vec<String>,
+    tags: WordTags,
+}
+
+// Labels carry no word positions, so a word is a run of phonemes in the same accent phrase
+// with the same previous, current and next word tags. Adjacent words can only run together if
+// all three are the same. Pauses and silences belong to none.
+fn label_words(labels: &[jlabel::Label]) -> Vec<LabelWord> {
+    let key = |label: &jlabel::Label| {
+        Some((
+            label_groups::accent_phrase_key(label)?,
+            label.word_prev.clone(),
+            label.word_curr.clone()?,
+            label.word_next.clone(),
+        ))
+    };
+    label_groups::group_by(labels, key)
+        .into_iter()
+        .map(|((_, _, word, _), range)| LabelWord {
+            phonemes: label_groups::phonemes(&labels[range]),
+            tags: WordTags::new(&word),
+        })
+        .collect()
+}
+
+// Align the two engines' words by their moras, so that devoicing does not count, and compare the
+// tags of the words that are pronounced the same. Words the engines pronounce differently are
+// left to the phoneme diff.
+pub fn diff_words(ojt_labels: &[jlabel::Label], jp_labels: &[jlabel::Label]) -> Vec<WordDiff> {
+    use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+    let ojt_words = label_words(ojt_labels);
+    let jp_words = label_words(jp_labels);
+    let moras = |words: &[LabelWord]| {
+        words
+            .iter()
+            .map(|w| mora::phonemes_to_moras(&w.phonemes))
+            .collect::<Vec<_>>()
+    };
+    let ojt_moras = moras(&ojt_words);
+    let jp_moras = moras(&jp_words);
+
+    let mut diffs = vec![];
+    for op in capture_diff_slices(Algorithm::Myers, &ojt_moras, &jp_moras) {
+        let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        else {
+            continue;
+        };
+        for offset in 0..len {
+            let (ojt, jp) = (
+                &ojt_words[old_index + offset],
+                &jp_words[new_index + offset],
+            );
+            if ojt.tags != jp.tags {
+                diffs.push(WordDiff {
+                    kana: mora::moras_to_kana(&ojt_moras[old_index + offset]),
+                    openjtalk: ojt.tags.clone(),
+                    jpreprocess: jp.tags.clone(),
+                });
+            }
+        }
+    }
+    diffs
+}
+
+// Words tagged with a different part of speech, by "OpenJTalk's / jpreprocess's" part of speech.
+pub fn count_pos_confusions(diffs: &[WordDiff], counts: &mut BTreeMap<String, usize>) {
+    for diff in diffs
+        .iter()
+        .filter(|d| d.openjtalk.pos != d.jpreprocess.pos)
+    {
+        *counts
+            .entry(format!("{} / {}", diff.openjtalk.pos, diff.jpreprocess.pos))
+            .or_default() += 1;
+    }
+}
+
+fn format_tags(tags: &WordTags) -> String {
+    format!("{} (ctype {}, cform {})", tags.pos, tags.ctype, tags.cform)
+}
+
+pub fn format_word_diff(diff: &WordDiff) -> String {
+    format!(
+        "{}: OpenJTalk {} / JPreprocess {}",
+        diff.kana,
+        format_tags(&diff.openjtalk),
+        format_tags(&diff.jpreprocess)
+    )
+}
+
+// The most frequent part-of-speech confusions first.
+pub fn top_pos_confusions(counts: &BTreeMap<String, usize>, limit: usize) -> Vec<(&str, usize)> {
+    let mut confusions = counts
+        .iter()
+        .map(|(pair, &count)| (pair.as_str(), count))
+        .collect::<Vec<_>>();
+    confusions.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    confusions.truncate(limit);
+    confusions
+}
@@ -0,0 +1,271 @@
+// Runs an engine in a child process (the same binary, started with the engine's worker argument),
+// so that crashes, aborts and stack overflows are recorded as errors instead of ending the whole run.
+use crate::stages::{self, EngineRun, Snapshots, StagesOutdated};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+pub const OPENJTALK_WORKER_ARG: &str = "--openjtalk-worker";
+#[cfg(feature = "panic_safe")]
+pub const JPREPROCESS_WORKER_ARG: &str = "--jpreprocess-worker";
+
+#[derive(Clone, Copy)]
+pub enum Engine {
+    OpenJtalk,
+    #[cfg(feature = "panic_safe")]
+    Jpreprocess,
+}
+
+impl Engine {
+    fn worker_arg(self) -> &'static str {
+        match self {
+            Engine::OpenJtalk => OPENJTALK_WORKER_ARG,
+            #[cfg(feature = "panic_safe")]
+            Engine::Jpreprocess => JPREPROCESS_WORKER_ARG,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Engine::OpenJtalk => "open_jtalk",
+            #[cfg(feature = "panic_safe")]
+            Engine::Jpreprocess => "jpreprocess",
+        }
+    }
+
+    // jpreprocess errors starting with "panicked!" are recorded as panics (see `error_entry`);
+    // OpenJTalk crashes are plain errors.
+    fn crash_message(self, message: String) -> String {
+        match self {
+            Engine::OpenJtalk => message,
+            #[cfg(feature = "panic_safe")]
+            Engine::Jpreprocess => format!("panicked! {}", message),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum Response {
+    Labels {
+        labels: Vec<String>,
+        snapshots: Result<Snapshots, String>,
+        duration_ms: f64,
+    },
+    // The comparison cannot go on (see `StagesOutdated`).
+    Fatal {
+        message: String,
+    },
+    Error {
+        message: String,
+        duration_ms: f64,
+    },
+    Panic {
+        message: String,
+        duration_ms: f64,
+    },
+}
+
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+// Panic payloads are `&'static str` for literal messages and `String` for formatted ones.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
+}
+
+// Remember the message and location of the last panic instead of printing it.
+fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = panic_message(info.payload());
+        let message = match info.location() {
+            Some(location) => format!("{} at {}", message, location),
+            None => message,
+        };
+        if let Ok(mut last_panic) = LAST_PANIC.lock() {
+            *last_panic = Some(message);
+        }
+    }));
+}
+
+// The worker loop: one JSON-encoded sentence per stdin line, one JSON response per stdout line.
+fn serve(mut run: impl FnMut(&str) -> anyhow::Result<EngineRun>) -> anyhow::Result<()> {
+    install_panic_hook();
+
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let sentence: String = serde_json::from_str(&line?)?;
+        let started = Instant::now();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run(&sentence)));
+        let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let response = match result {
+            Ok(Ok(run)) => Response::Labels {
+                labels: run.labels.iter().map(ToString::to_string).collect(),
+                snapshots: run.snapshots.map_err(|e| e.to_string()),
+                duration_ms,
+            },
+            Ok(Err(e)) if e.is::<StagesOutdated>() => Response::Fatal {
+                message: e.to_string(),
+            },
+            Ok(Err(e)) => Response::Error {
+                message: e.to_string(),
+                duration_ms,
+            },
+            Err(payload) => Response::Panic {
+                message: LAST_PANIC
+                    .lock()
+                    .ok()
+                    .and_then(|mut last_panic| last_panic.take())
+                    .unwrap_or_else(|| panic_message(payload.as_ref())),
+                duration_ms,
+            },
+        };
+        serde_json::to_writer(&mut stdout, &response)?;
+        writeln!(stdout)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+pub fn run_openjtalk_worker() -> anyhow::Result<()> {
+    crate::load_openjtalk()?;
+    serve(stages::run_openjtalk)
+}
+
+#[cfg(feature = "panic_safe")]
+pub fn run_jpreprocess_worker() -> anyhow::Result<()> {
+    let jp = jpreprocess::JPreprocess::with_dictionaries(
+        jpreprocess::SystemDictionaryConfig::Bundled(
+            jpreprocess::kind::JPreprocessDictionaryKind::NaistJdic,
+        )
+        .load()?,
+        None,
+    );
+    serve(|sentence| {
+        stages::run_jpreprocess(sentence, |sentence| {
+            jp.text_to_njd(sentence).map_err(anyhow::Error::from)
+        })
+    })
+}
+
+pub struct Extraction {
+    pub run: anyhow::Result<EngineRun>,
+    // Time spent inside the engine, excluding the round trip to the worker.
+    pub duration_ms: f64,
+}
+
+pub struct Worker {
+    engine: Engine,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Worker {
+    pub fn spawn(engine: Engine) -> anyhow::Result<Self> {
+        let mut child = Command::new(std::env::current_exe()?)
+            .arg(engine.worker_arg())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Self {
+            engine,
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn request(&mut self, sentence: &str) -> std::io::Result<Option<Response>> {
+        serde_json::to_writer(&mut self.stdin, sentence)?;
+        writeln!(self.stdin)?;
+        self.stdin.flush()?;
+
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+
+    fn restart(&mut self) -> anyhow::Result<()> {
+        // Dropping the old worker kills it.
+        *self = Self::spawn(self.engine)?;
+        Ok(())
+    }
+
+    // Panics and crashes are reported as errors, and the worker is restarted
+    // afterwards so that the next sentence starts from a fresh engine instance.
+    pub fn run(&mut self, sentence: &str) -> anyhow::Result<Extraction> {
+        let started = Instant::now();
+        let response = self.request(sentence);
+        let extraction = match response {
+            Ok(Some(Response::Labels {
+                labels,
+                snapshots,
+                duration_ms,
+            })) => Extraction {
+                run: labels
+                    .iter()
+                    .map(|l| jlabel::Label::from_str(l).map_err(anyhow::Error::from))
+                    .collect::<anyhow::Result<_>>()
+                    .map(|labels| EngineRun {
+                        labels,
+                        snapshots: snapshots.map_err(anyhow::Error::msg),
+                    }),
+                duration_ms,
+            },
+            Ok(Some(Response::Fatal { message })) => anyhow::bail!(message),
+            Ok(Some(Response::Error {
+                message,
+                duration_ms,
+            })) => Extraction {
+                run: Err(anyhow::anyhow!(message)),
+                duration_ms,
+            },
+            Ok(Some(Response::Panic {
+                message,
+                duration_ms,
+            })) => {
+                self.restart()?;
+                Extraction {
+                    run: Err(anyhow::anyhow!(self.engine.crash_message(message))),
+                    duration_ms,
+                }
+            }
+            Ok(None) | Err(_) => {
+                // The worker is gone, or is sending garbage; in the latter case kill it.
+                // Killing an already exited worker keeps its original exit status,
+                // e.g. "signal: 11 (SIGSEGV)".
+                let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+                let _ = self.child.kill();
+                let status = self.child.wait()?;
+                self.restart()?;
+                Extraction {
+                    run: Err(anyhow::anyhow!(self.engine.crash_message(format!(
+                        "{} worker died ({})",
+                        self.engine.name(),
+                        status
+                    )))),
+                    duration_ms,
+                }
+            }
+        };
+        Ok(extraction)
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}